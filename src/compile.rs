@@ -0,0 +1,32 @@
+use crate::error::Diagnostic;
+use crate::fold::fold_constants;
+use crate::node::Node;
+use crate::parser::{parse_program, ParsingContext};
+use crate::resolver::Resolver;
+use crate::span::FileId;
+use crate::typechecker::TypeChecker;
+
+/// Run every pass over `source`: parsing, name resolution and type
+/// checking, then constant folding if those found no errors. Each checking
+/// pass runs even after an earlier one fails, so that all of a file's
+/// diagnostics are reported together.
+pub fn compile(source: &str, file: FileId) -> (Node, Vec<Diagnostic>) {
+    let mut context = ParsingContext::new();
+    let mut program = parse_program(&mut context, source, file);
+    let mut resolver = Resolver::new();
+    resolver.resolve_program(&program);
+    context.diagnostics.append(&mut resolver.diagnostics);
+    let mut type_checker = TypeChecker::new();
+    type_checker.check_program(&mut program);
+    context.diagnostics.append(&mut type_checker.diagnostics);
+    if context.diagnostics.is_empty() {
+        fold_constants(&mut program);
+    }
+    (program, context.diagnostics)
+}
+
+/// The codes of every diagnostic from compiling `source`, in order.
+#[cfg(test)]
+pub fn codes(source: &str) -> Vec<crate::error_codes::ErrorCode> {
+    compile(source, 0).1.iter().map(|err| err.code).collect()
+}
@@ -2,6 +2,8 @@
 use std::collections::HashMap;
 use crate::node::{Node, NodeValue};
 
+/// A chain of scopes. `bind` holds the innermost scope and `parent` the
+/// enclosing ones; lookups walk outwards so inner bindings shadow outer ones.
 #[derive(Debug)]
 pub struct Environment {
     pub parent: Option<Box<Environment>>,
@@ -16,6 +18,21 @@ impl Environment {
         }
     }
 
+    /// Enter a new innermost scope.
+    pub fn push_scope(&mut self) {
+        let parent = std::mem::replace(self, Environment::new(None));
+        self.parent = Some(Box::new(parent));
+    }
+
+    /// Leave the innermost scope, dropping its bindings. The outermost
+    /// scope is never popped.
+    pub fn pop_scope(&mut self) {
+        if let Some(parent) = self.parent.take() {
+            *self = *parent;
+        }
+    }
+
+    /// Bind `id` in the innermost scope, shadowing any outer binding.
     pub fn set(&mut self, id: &Node, value: Node) -> Result<(), String> {
         if let Some(NodeValue::Symbol(ref id_str)) = id.value {
             self.bind.insert(id_str.clone(), value);
@@ -25,11 +42,86 @@ impl Environment {
         }
     }
 
-    pub fn get(&self, id: &Node) -> Option<&Node> {
+    /// Rebind `id` in the nearest scope that already binds it.
+    pub fn assign(&mut self, id: &Node, value: Node) -> Result<(), String> {
+        let Some(NodeValue::Symbol(ref id_str)) = id.value else {
+            return Err("Invalid ID type".to_string());
+        };
+        if let Some(bound) = self.bind.get_mut(id_str) {
+            *bound = value;
+            Ok(())
+        } else if let Some(ref mut parent) = self.parent {
+            parent.assign(id, value)
+        } else {
+            Err(format!("Unbound ID: {}", id_str))
+        }
+    }
+
+    /// Look `id` up in the innermost scope only.
+    pub fn get_local(&self, id: &Node) -> Option<&Node> {
         if let Some(NodeValue::Symbol(ref id_str)) = id.value {
             self.bind.get(id_str)
         } else {
             None
         }
     }
+
+    /// Look `id` up, walking outwards through the enclosing scopes.
+    pub fn get(&self, id: &Node) -> Option<&Node> {
+        self.get_local(id)
+            .or_else(|| self.parent.as_ref().and_then(|parent| parent.get(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The integer `name` is bound to, if any.
+    fn lookup(env: &Environment, name: &str) -> Option<i128> {
+        match env.get(&Node::from_symbol(name))?.value {
+            Some(NodeValue::Integer(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn an_inner_binding_shadows_until_its_scope_is_popped() {
+        let x = Node::from_symbol("x");
+        let mut env = Environment::new(None);
+        env.set(&x, Node::from_integer(1)).unwrap();
+        env.push_scope();
+        assert_eq!(lookup(&env, "x"), Some(1));
+        assert!(env.get_local(&x).is_none());
+        env.set(&x, Node::from_integer(2)).unwrap();
+        assert_eq!(lookup(&env, "x"), Some(2));
+        env.pop_scope();
+        assert_eq!(lookup(&env, "x"), Some(1));
+    }
+
+    #[test]
+    fn assign_rebinds_the_nearest_enclosing_binding() {
+        let (x, y) = (Node::from_symbol("x"), Node::from_symbol("y"));
+        let mut env = Environment::new(None);
+        env.set(&x, Node::from_integer(1)).unwrap();
+        env.push_scope();
+        env.push_scope();
+        env.assign(&x, Node::from_integer(3)).unwrap();
+        assert!(env.get_local(&x).is_none());
+        env.pop_scope();
+        env.pop_scope();
+        assert_eq!(lookup(&env, "x"), Some(3));
+        assert_eq!(env.assign(&y, Node::from_integer(4)), Err("Unbound ID: y".to_string()));
+        assert!(env.set(&Node::from_integer(5), Node::from_integer(6)).is_err());
+    }
+
+    #[test]
+    fn popping_the_outermost_scope_keeps_its_bindings() {
+        let mut env = Environment::new(None);
+        env.set(&Node::from_symbol("x"), Node::from_integer(1)).unwrap();
+        env.pop_scope();
+        env.pop_scope();
+        assert_eq!(lookup(&env, "x"), Some(1));
+        assert!(env.parent.is_none());
+    }
 }
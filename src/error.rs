@@ -1,50 +1,456 @@
-#[derive(Debug, PartialEq)]
-enum ErrorType {
+use std::io::IsTerminal;
+
+use crate::error_codes::ErrorCode;
+use crate::span::{SourceMap, Span};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorType {
     ErrorNone = 0,
     ErrorArguments,
     ErrorType,
     ErrorGeneric,
     ErrorSyntax,
     ErrorTodo,
+    #[allow(dead_code)] // a count of the categories, never a category itself
     ErrorMax,
 }
 
-#[derive(Debug)]
-struct Error {
-    error_type: ErrorType,
-    msg: Option<String>,
+impl ErrorType {
+    /// The category name used in machine-readable output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorType::ErrorNone => "none",
+            ErrorType::ErrorArguments => "arguments",
+            ErrorType::ErrorType => "type",
+            ErrorType::ErrorGeneric => "generic",
+            ErrorType::ErrorSyntax => "syntax",
+            ErrorType::ErrorTodo => "todo",
+            ErrorType::ErrorMax => "max",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    // No pass reports these yet, but both renderers already handle them.
+    #[allow(dead_code)]
+    Warning,
+    #[allow(dead_code)]
+    Note,
 }
 
-impl Error {
-    fn new(error_type: ErrorType, msg: Option<String>) -> Self {
-        Error { error_type, msg }
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
     }
 }
 
-fn print_error(err: &Error) {
-    if err.error_type == ErrorType::ErrorNone {
-        return;
+/// A secondary span worth pointing at, such as an earlier declaration.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A machine-applicable fix: replace the text at `span` with `replacement`.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub error_type: ErrorType,
+    pub message: String,
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: ErrorCode, span: Span, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            error_type: code.error_type(),
+            message: message.into(),
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(code: ErrorCode, span: Span, message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Error, code, span, message)
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
     }
-    print!("ERROR: ");
-    assert!(ErrorType::ErrorMax as i32 == 6);
-    match err.error_type {
-        ErrorType::ErrorTodo => println!("TODO (not implemented)"),
-        ErrorType::ErrorSyntax => println!("Invalid syntax"),
-        ErrorType::ErrorType => println!("Mismatched types"),
-        ErrorType::ErrorArguments => println!("Invalid arguments"),
-        ErrorType::ErrorGeneric => {},
-        _ => println!("Unknown error type..."),
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
     }
-    if let Some(ref msg) = err.msg {
-        println!("     : {}", msg);
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, span: Span, replacement: impl Into<String>, message: impl Into<String>) -> Self {
+        self.suggestions.push(Suggestion { span, replacement: replacement.into(), message: message.into() });
+        self
     }
 }
 
-fn main() {
-    let ok = Error::new(ErrorType::ErrorNone, None);
-    let syntax_error = Error::new(ErrorType::ErrorSyntax, Some("Unexpected token".to_string()));
-    
-    print_error(&ok);
-    print_error(&syntax_error);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
+impl ColorChoice {
+    pub fn from_flag(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    /// Whether to colour output written to stderr.
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal(),
+        }
+    }
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn severity(&self, severity: Severity, text: &str) -> String {
+        let code = match severity {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+            Severity::Note => "1;36",
+        };
+        self.paint(code, text)
+    }
+
+    fn gutter(&self, text: &str) -> String {
+        self.paint("1;34", text)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+}
+
+/// Render `err` the way rustc does: a header, the location, and each
+/// source line involved with the primary span underlined by `^` and any
+/// labels underlined by `-`.
+pub fn render_error(source_map: &SourceMap, err: &Diagnostic, color: bool) -> String {
+    let style = Style { color };
+    let file = source_map.file(err.span.file);
+
+    let mut annotations = vec![(err.span, true, "")];
+    annotations.extend(
+        err.labels
+            .iter()
+            .filter(|label| label.span.file == err.span.file)
+            .map(|label| (label.span, false, label.message.as_str())),
+    );
+    let mut lines: Vec<usize> = annotations.iter().map(|(span, _, _)| span.line).collect();
+    lines.sort_unstable();
+    lines.dedup();
+
+    let width = lines.last().map_or(1, |line| line.to_string().len());
+    let pad = " ".repeat(width);
+    let mut out = String::new();
+    out += &format!(
+        "{}{}\n",
+        style.severity(err.severity, &format!("{}[{}]", err.severity.name(), err.code.code())),
+        style.bold(&format!(": {}", err.message))
+    );
+    out += &format!("{}{} {}\n", pad, style.gutter("-->"), source_map.location(err.span));
+    // A diagnostic about a file as a whole, such as one that could not be
+    // read, has no source to show.
+    if file.source.is_empty() {
+        return out;
+    }
+    out += &format!("{} {}\n", pad, style.gutter("|"));
+
+    for line in lines {
+        let text = file.line_text(line);
+        out += &format!("{} {}\n", style.gutter(&format!("{:>width$} |", line, width = width)), text);
+        for (span, primary, message) in annotations.iter().filter(|(span, _, _)| span.line == line) {
+            let prefix: String = text
+                .chars()
+                .take(span.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let remaining = text.chars().count().saturating_sub(span.column - 1);
+            let length = source_map.file(span.file).source[span.start..span.end].chars().count();
+            let marker = if *primary { "^" } else { "-" };
+            let underline = marker.repeat(length.min(remaining).max(1));
+            let annotation = if message.is_empty() {
+                underline
+            } else {
+                format!("{} {}", underline, message)
+            };
+            let annotation = if *primary {
+                style.severity(err.severity, &annotation)
+            } else {
+                style.gutter(&annotation)
+            };
+            out += &format!("{} {} {}{}\n", pad, style.gutter("|"), prefix, annotation);
+        }
+    }
+
+    for label in err.labels.iter().filter(|label| label.span.file != err.span.file) {
+        out += &format!("{} {} {}: {}\n", pad, style.gutter("-->"), source_map.location(label.span), label.message);
+    }
+    for note in &err.notes {
+        out += &format!("{} {} {}\n", pad, style.gutter("="), style.bold(&format!("note: {}", note)));
+    }
+    if let Some(ref help) = err.help {
+        out += &format!("{} {} {}\n", pad, style.gutter("="), style.bold(&format!("help: {}", help)));
+    }
+    for suggestion in &err.suggestions {
+        // An empty replacement is a removal; there is nothing to quote.
+        let help = if suggestion.replacement.is_empty() {
+            format!("help: {}", suggestion.message)
+        } else {
+            format!("help: {}: `{}`", suggestion.message, suggestion.replacement)
+        };
+        out += &format!("{} {} {}\n", pad, style.gutter("="), style.bold(&help));
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn from_flag(value: &str) -> Option<Self> {
+        match value {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_span(source_map: &SourceMap, span: Span) -> String {
+    let (end_line, end_column) = source_map.line_column(span.file, span.end);
+    format!(
+        "{{\"file\":{},\"start\":{{\"offset\":{},\"line\":{},\"column\":{}}},\"end\":{{\"offset\":{},\"line\":{},\"column\":{}}}}}",
+        json_string(&source_map.file(span.file).name),
+        span.start,
+        span.line,
+        span.column,
+        span.end,
+        end_line,
+        end_column
+    )
+}
+
+/// Render `err` as a single-line JSON object. Every key is always present
+/// so that consumers can rely on the schema:
+///
+/// `{"code", "category", "severity", "message", "span", "labels": [{"message", "span"}],
+/// "notes": [...], "help", "suggestions": [{"message", "replacement", "span"}]}`
+///
+/// where each span is `{"file", "start": {"offset", "line", "column"}, "end": {...}}`.
+pub fn render_json(source_map: &SourceMap, err: &Diagnostic) -> String {
+    let labels: Vec<String> = err
+        .labels
+        .iter()
+        .map(|label| {
+            format!(
+                "{{\"message\":{},\"span\":{}}}",
+                json_string(&label.message),
+                json_span(source_map, label.span)
+            )
+        })
+        .collect();
+    let notes: Vec<String> = err.notes.iter().map(|note| json_string(note)).collect();
+    let suggestions: Vec<String> = err
+        .suggestions
+        .iter()
+        .map(|suggestion| {
+            format!(
+                "{{\"message\":{},\"replacement\":{},\"span\":{}}}",
+                json_string(&suggestion.message),
+                json_string(&suggestion.replacement),
+                json_span(source_map, suggestion.span)
+            )
+        })
+        .collect();
+    format!(
+        "{{\"code\":{},\"category\":{},\"severity\":{},\"message\":{},\"span\":{},\"labels\":[{}],\"notes\":[{}],\"help\":{},\"suggestions\":[{}]}}",
+        json_string(err.code.code()),
+        json_string(err.error_type.name()),
+        json_string(err.severity.name()),
+        json_string(&err.message),
+        json_span(source_map, err.span),
+        labels.join(","),
+        notes.join(","),
+        err.help.as_deref().map_or("null".to_string(), json_string),
+        suggestions.join(",")
+    )
+}
+
+pub fn print_error(source_map: &SourceMap, err: &Diagnostic, format: ErrorFormat, color: ColorChoice) {
+    if err.error_type == ErrorType::ErrorNone {
+        return;
+    }
+    match format {
+        ErrorFormat::Human => eprintln!("{}", render_error(source_map, err, color.enabled())),
+        ErrorFormat::Json => eprintln!("{}", render_json(source_map, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "a : integr = 1\nfoo(1,)\n";
+
+    fn source_map() -> SourceMap {
+        let mut source_map = SourceMap::new();
+        source_map.add_file("test.cl", SOURCE.to_string());
+        source_map
+    }
+
+    #[test]
+    fn renders_every_key() {
+        let span = Span::new(0, 4, 10, 1, 5);
+        let err = Diagnostic::error(ErrorCode::UnknownType, span, "Invalid type within variable declaration: integr")
+            .with_label(Span::new(0, 0, 1, 1, 1), "declared here")
+            .with_note("the built-in types include integer")
+            .with_help("check the spelling")
+            .with_suggestion(span, "integer", "use a known type");
+        assert_eq!(
+            render_json(&source_map(), &err),
+            concat!(
+                r#"{"code":"CL0001","category":"type","severity":"error","#,
+                r#""message":"Invalid type within variable declaration: integr","#,
+                r#""span":{"file":"test.cl","start":{"offset":4,"line":1,"column":5},"end":{"offset":10,"line":1,"column":11}},"#,
+                r#""labels":[{"message":"declared here","span":{"file":"test.cl","start":{"offset":0,"line":1,"column":1},"end":{"offset":1,"line":1,"column":2}}}],"#,
+                r#""notes":["the built-in types include integer"],"help":"check the spelling","#,
+                r#""suggestions":[{"message":"use a known type","replacement":"integer","span":{"file":"test.cl","start":{"offset":4,"line":1,"column":5},"end":{"offset":10,"line":1,"column":11}}}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn renders_an_empty_suggestion() {
+        let comma = Span::new(0, 20, 21, 2, 6);
+        let err = Diagnostic::error(ErrorCode::TrailingComma, comma, "Trailing comma in argument list")
+            .with_suggestion(comma, "", "remove the comma");
+        assert_eq!(
+            render_json(&source_map(), &err),
+            concat!(
+                r#"{"code":"CL0027","category":"syntax","severity":"error","message":"Trailing comma in argument list","#,
+                r#""span":{"file":"test.cl","start":{"offset":20,"line":2,"column":6},"end":{"offset":21,"line":2,"column":7}},"#,
+                r#""labels":[],"notes":[],"help":null,"#,
+                r#""suggestions":[{"message":"remove the comma","replacement":"","span":{"file":"test.cl","start":{"offset":20,"line":2,"column":6},"end":{"offset":21,"line":2,"column":7}}}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn renders_missing_help_as_null() {
+        let err = Diagnostic::error(ErrorCode::UndefinedFunction, Span::new(0, 15, 22, 2, 1), "Call to undefined function: foo");
+        assert_eq!(
+            render_json(&source_map(), &err),
+            concat!(
+                r#"{"code":"CL0010","category":"generic","severity":"error","message":"Call to undefined function: foo","#,
+                r#""span":{"file":"test.cl","start":{"offset":15,"line":2,"column":1},"end":{"offset":22,"line":2,"column":8}},"#,
+                r#""labels":[],"notes":[],"help":null,"suggestions":[]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn renders_a_file_without_source() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file("missing.cl", String::new());
+        let err = Diagnostic::error(ErrorCode::UnreadableFile, Span::new(file, 0, 0, 1, 1), "Failed to read missing.cl");
+        assert_eq!(
+            render_error(&source_map, &err, false),
+            "error[CL0035]: Failed to read missing.cl\n --> missing.cl:1:1\n"
+        );
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let err = Diagnostic::error(
+            ErrorCode::UnexpectedCharacter,
+            Span::new(0, 0, 1, 1, 1),
+            "quote \" backslash \\ newline \n tab \t bell \u{7} snowman \u{2603}",
+        );
+        assert_eq!(
+            render_json(&source_map(), &err),
+            concat!(
+                r#"{"code":"CL0002","category":"syntax","severity":"error","#,
+                r#""message":"quote \" backslash \\ newline \n tab \t bell \u0007 snowman ☃","#,
+                r#""span":{"file":"test.cl","start":{"offset":0,"line":1,"column":1},"end":{"offset":1,"line":1,"column":2}},"#,
+                r#""labels":[],"notes":[],"help":null,"suggestions":[]}"#,
+            )
+        );
+    }
+}
@@ -0,0 +1,791 @@
+use crate::error::ErrorType;
+
+/// Every distinct diagnostic the compiler can emit. Codes are stable: once
+/// assigned, a code keeps its meaning and is never reused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    UnknownType,
+    UnexpectedCharacter,
+    UnterminatedBlockComment,
+    UnexpectedToken,
+    InvalidIntegerLiteral,
+    UnterminatedBlock,
+    UnmatchedBrace,
+    ExpectedTopLevelForm,
+    LexPastEnd,
+    UndefinedFunction,
+    ArgumentCount,
+    UndefinedVariable,
+    ArithmeticOverflow,
+    DivisionByZero,
+    Unsupported,
+    DuplicateDeclaration,
+    DuplicateParameter,
+    TypeMismatch,
+    VoidValue,
+    BreakOutsideLoop,
+    InvalidFloatLiteral,
+    UnterminatedString,
+    InvalidEscape,
+    InvalidCharLiteral,
+    UnknownField,
+    MissingField,
+    TrailingComma,
+    InvalidAssignmentTarget,
+    IntegerOutOfRange,
+    FloatOutOfRange,
+    UnsignedNegation,
+    InvalidCast,
+    NestedDefinition,
+    InvalidMain,
+    UnreadableFile,
+    InvalidExitStatus,
+}
+
+pub const ALL_ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode::UnknownType,
+    ErrorCode::UnexpectedCharacter,
+    ErrorCode::UnterminatedBlockComment,
+    ErrorCode::UnexpectedToken,
+    ErrorCode::InvalidIntegerLiteral,
+    ErrorCode::UnterminatedBlock,
+    ErrorCode::UnmatchedBrace,
+    ErrorCode::ExpectedTopLevelForm,
+    ErrorCode::LexPastEnd,
+    ErrorCode::UndefinedFunction,
+    ErrorCode::ArgumentCount,
+    ErrorCode::UndefinedVariable,
+    ErrorCode::ArithmeticOverflow,
+    ErrorCode::DivisionByZero,
+    ErrorCode::Unsupported,
+    ErrorCode::DuplicateDeclaration,
+    ErrorCode::DuplicateParameter,
+    ErrorCode::TypeMismatch,
+    ErrorCode::VoidValue,
+    ErrorCode::BreakOutsideLoop,
+    ErrorCode::InvalidFloatLiteral,
+    ErrorCode::UnterminatedString,
+    ErrorCode::InvalidEscape,
+    ErrorCode::InvalidCharLiteral,
+    ErrorCode::UnknownField,
+    ErrorCode::MissingField,
+    ErrorCode::TrailingComma,
+    ErrorCode::InvalidAssignmentTarget,
+    ErrorCode::IntegerOutOfRange,
+    ErrorCode::FloatOutOfRange,
+    ErrorCode::UnsignedNegation,
+    ErrorCode::InvalidCast,
+    ErrorCode::NestedDefinition,
+    ErrorCode::InvalidMain,
+    ErrorCode::UnreadableFile,
+    ErrorCode::InvalidExitStatus,
+];
+
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::UnknownType => "CL0001",
+            ErrorCode::UnexpectedCharacter => "CL0002",
+            ErrorCode::UnterminatedBlockComment => "CL0003",
+            ErrorCode::UnexpectedToken => "CL0004",
+            ErrorCode::InvalidIntegerLiteral => "CL0005",
+            ErrorCode::UnterminatedBlock => "CL0006",
+            ErrorCode::UnmatchedBrace => "CL0007",
+            ErrorCode::ExpectedTopLevelForm => "CL0008",
+            ErrorCode::LexPastEnd => "CL0009",
+            ErrorCode::UndefinedFunction => "CL0010",
+            ErrorCode::ArgumentCount => "CL0011",
+            ErrorCode::UndefinedVariable => "CL0012",
+            ErrorCode::ArithmeticOverflow => "CL0013",
+            ErrorCode::DivisionByZero => "CL0014",
+            ErrorCode::Unsupported => "CL0015",
+            ErrorCode::DuplicateDeclaration => "CL0016",
+            ErrorCode::DuplicateParameter => "CL0017",
+            ErrorCode::TypeMismatch => "CL0018",
+            ErrorCode::VoidValue => "CL0019",
+            ErrorCode::BreakOutsideLoop => "CL0020",
+            ErrorCode::InvalidFloatLiteral => "CL0021",
+            ErrorCode::UnterminatedString => "CL0022",
+            ErrorCode::InvalidEscape => "CL0023",
+            ErrorCode::InvalidCharLiteral => "CL0024",
+            ErrorCode::UnknownField => "CL0025",
+            ErrorCode::MissingField => "CL0026",
+            ErrorCode::TrailingComma => "CL0027",
+            ErrorCode::InvalidAssignmentTarget => "CL0028",
+            ErrorCode::IntegerOutOfRange => "CL0029",
+            ErrorCode::FloatOutOfRange => "CL0030",
+            ErrorCode::UnsignedNegation => "CL0031",
+            ErrorCode::InvalidCast => "CL0032",
+            ErrorCode::NestedDefinition => "CL0033",
+            ErrorCode::InvalidMain => "CL0034",
+            ErrorCode::UnreadableFile => "CL0035",
+            ErrorCode::InvalidExitStatus => "CL0036",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        ALL_ERROR_CODES.iter().copied().find(|error_code| error_code.code() == code)
+    }
+
+    /// The coarse category this code belongs to.
+    pub fn error_type(&self) -> ErrorType {
+        match self {
+            ErrorCode::UnknownType
+            | ErrorCode::TypeMismatch
+            | ErrorCode::VoidValue
+            | ErrorCode::UnknownField
+            | ErrorCode::MissingField
+            | ErrorCode::IntegerOutOfRange
+            | ErrorCode::FloatOutOfRange
+            | ErrorCode::UnsignedNegation
+            | ErrorCode::InvalidCast
+            | ErrorCode::InvalidMain => ErrorType::ErrorType,
+            ErrorCode::ArgumentCount => ErrorType::ErrorArguments,
+            ErrorCode::Unsupported => ErrorType::ErrorTodo,
+            ErrorCode::LexPastEnd
+            | ErrorCode::UndefinedFunction
+            | ErrorCode::UndefinedVariable
+            | ErrorCode::ArithmeticOverflow
+            | ErrorCode::DivisionByZero
+            | ErrorCode::DuplicateDeclaration
+            | ErrorCode::BreakOutsideLoop
+            | ErrorCode::UnreadableFile
+            | ErrorCode::InvalidExitStatus => ErrorType::ErrorGeneric,
+            ErrorCode::DuplicateParameter => ErrorType::ErrorArguments,
+            ErrorCode::UnexpectedCharacter
+            | ErrorCode::UnterminatedBlockComment
+            | ErrorCode::UnexpectedToken
+            | ErrorCode::InvalidIntegerLiteral
+            | ErrorCode::UnterminatedBlock
+            | ErrorCode::UnmatchedBrace
+            | ErrorCode::ExpectedTopLevelForm
+            | ErrorCode::InvalidFloatLiteral
+            | ErrorCode::UnterminatedString
+            | ErrorCode::InvalidEscape
+            | ErrorCode::InvalidCharLiteral
+            | ErrorCode::TrailingComma
+            | ErrorCode::InvalidAssignmentTarget
+            | ErrorCode::NestedDefinition => ErrorType::ErrorSyntax,
+        }
+    }
+
+    /// The long-form explanation printed by `--explain`.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnknownType => {
+                "A declaration named a type that does not exist.
+
+Erroneous code example:
+
+    a : integr = 69
+    defun double (x : integr) : integr { x * 2 }
+
+Every type used in a variable declaration, parameter list, return type or
+struct field must be a built-in type such as `integer`, or a struct
+defined earlier in the file. Check the spelling of the type name:
+
+    a : integer = 69
+    defun double (x : integer) : integer { x * 2 }"
+            }
+            ErrorCode::UnexpectedCharacter => {
+                "The source contains a character that cannot start any token.
+
+Erroneous code example:
+
+    a := 1 $ 2
+
+Only letters, digits, `_`, whitespace, comments and the operators of the
+language may appear outside of comments. Remove the character or replace
+it with the intended operator:
+
+    a := 1 + 2"
+            }
+            ErrorCode::UnterminatedBlockComment => {
+                "A `/*` block comment was never closed.
+
+Erroneous code example:
+
+    /* the answer /* nested */
+    a : integer = 42
+
+Block comments nest, so every `/*` needs its own matching `*/`:
+
+    /* the answer /* nested */ */
+    a : integer = 42"
+            }
+            ErrorCode::UnexpectedToken => {
+                "The parser found a token where a different one was required.
+
+Erroneous code example:
+
+    defun foo (a integer) { }
+
+The message names what was expected. Here every parameter needs a `:`
+between its name and its type:
+
+    defun foo (a : integer) { }"
+            }
+            ErrorCode::InvalidIntegerLiteral => {
+                "An integer literal is malformed.
+
+Erroneous code example:
+
+    a : integer = 12ab
+    b : u8 = 0b102
+    c : integer = 0x
+    d : u64 = 99999999999999999999
+    e : integer = 1__0_
+
+An integer literal is a run of decimal digits, or of hexadecimal, octal or
+binary digits after a `0x`, `0o` or `0b` prefix. Each `_` must separate two
+digits, as in `1_000_000`. A literal may end with one of the type suffixes
+i8, i16, i32, i64, u8, u16, u32 or u64, and no literal may be larger than
+the largest `u64`:
+
+    a : integer = 12
+    b : u8 = 0b101
+    c : integer = 0x0
+    d : u64 = 9999999999999999999"
+            }
+            ErrorCode::UnterminatedBlock => {
+                "The file ended before a block was closed.
+
+Erroneous code example:
+
+    defun foo () {
+        while a < 10 {
+            a := a + 1
+    }
+
+Close every function body, `if`, `else` and `while` block with a `}`:
+
+    defun foo () {
+        while a < 10 {
+            a := a + 1
+        }
+    }"
+            }
+            ErrorCode::UnmatchedBrace => {
+                "A `}` appeared without a matching `{`.
+
+Erroneous code example:
+
+    a : integer = 1
+    }
+
+Remove the stray brace, or add the `{` that should open the block."
+            }
+            ErrorCode::ExpectedTopLevelForm => {
+                "An expression appeared where only declarations may go.
+
+Erroneous code example:
+
+    a : integer = 1 2
+
+The top level of a file may only contain declarations, function and
+struct definitions, and assignments. Here `2` is left over after the
+declaration; remove it, or combine it into the initializer with an
+operator:
+
+    a : integer = 1 + 2"
+            }
+            ErrorCode::LexPastEnd => {
+                "The lexer was asked for a token past the end of the source.
+
+This is a bug in the compiler rather than in the program being compiled.
+Please report it along with the source file that triggered it."
+            }
+            ErrorCode::UndefinedFunction => {
+                "A function was called that has not been defined.
+
+Erroneous code example:
+
+    defun main () : integer {
+        return dobule(21)
+    }
+
+Every function called must be defined with `defun` somewhere in the same
+file, before or after the call. Check the spelling of the function name:
+
+    defun double (n : integer) : integer { n * 2 }
+
+    defun main () : integer {
+        return double(21)
+    }"
+            }
+            ErrorCode::ArgumentCount => {
+                "A function was called with the wrong number of arguments.
+
+Erroneous code example:
+
+    defun add (a : integer, b : integer) : integer { a + b }
+    defun main () : integer { add(1) }
+
+Pass exactly one argument for every parameter of the function:
+
+    defun main () : integer { add(1, 2) }"
+            }
+            ErrorCode::UndefinedVariable => {
+                "A variable was used or assigned that has not been declared.
+
+Erroneous code example:
+
+    a : integer = b + 1
+    c := 2
+
+Declare every variable before using it, including before assigning to it
+with `:=`:
+
+    b : integer = 41
+    a : integer = b + 1
+    c : integer
+    c := 2
+
+A variable declared inside a function body is only visible inside that
+function."
+            }
+            ErrorCode::ArithmeticOverflow => {
+                "An arithmetic operation produced a value that does not fit in its type.
+
+Erroneous code example:
+
+    a : integer = 9223372036854775807 + 1
+
+Integer arithmetic is checked at the width of its type: a result that
+does not fit, such as `200u8 + 100u8`, stops the program. Use smaller
+values, or a wider type. A left shift instead discards the bits shifted
+out, but shifting by a negative amount or by at least the width of the
+type is an overflow:
+
+    a : integer = 1 << 64
+
+Float arithmetic never overflows: a result too large for its type is an
+infinity, as in IEEE 754."
+            }
+            ErrorCode::DivisionByZero => {
+                "An integer was divided by zero.
+
+Erroneous code example:
+
+    b : integer = 0
+    a : integer = 10 / b
+
+Both `/` and `%` fail when their right-hand side is zero. Check the
+divisor before dividing."
+            }
+            ErrorCode::DuplicateDeclaration => {
+                "A name was declared twice in the same scope.
+
+Erroneous code example:
+
+    a : integer
+    a : integer = 2
+
+Each variable may be declared only once per scope, each function and
+struct only once per program, and each field only once per struct.
+Assign to the existing variable instead, or pick a new name:
+
+    a : integer
+    a := 2
+
+A declaration inside a function body may shadow a global or a parameter
+of the same name."
+            }
+            ErrorCode::DuplicateParameter => {
+                "A function has two parameters with the same name.
+
+Erroneous code example:
+
+    defun add (a : integer, a : integer) : integer { a + a }
+
+Give every parameter a distinct name:
+
+    defun add (a : integer, b : integer) : integer { a + b }"
+            }
+            ErrorCode::TypeMismatch => {
+                "A value has a different type from the one required where it is used.
+
+Erroneous code example:
+
+    defun answer () : integer {
+        a : integer = 42
+    }
+
+Initializers and assigned values must have the declared type of their
+variable, operands must have a type the operator accepts, `if` and
+`while` conditions must be `boolean` rather than `integer`, and a function
+with a return type must end with an expression of that type. A
+declaration or assignment is a statement and has no value, so here the
+function should end with the value itself:
+
+    defun answer () : integer {
+        a : integer = 42
+        a
+    }
+
+Integers and floats are never converted implicitly, so `1.5 * 2` is an
+error. Write `1.5 * 2.0`, or convert the value with an `as` cast, as in
+`n as f64`."
+            }
+            ErrorCode::VoidValue => {
+                "A variable or parameter was declared with type `void`.
+
+Erroneous code example:
+
+    a : void
+    defun ignore (x : void) { }
+
+`void` has no values, so nothing can be stored in it. It is only valid as
+the return type of a function that does not produce a value, which is
+also the default when the return type is left out:
+
+    defun ignore (x : integer) : void { }"
+            }
+            ErrorCode::BreakOutsideLoop => {
+                "A `break` or `continue` appeared outside of any loop.
+
+Erroneous code example:
+
+    defun first () : integer {
+        if true {
+            break
+        }
+        0
+    }
+
+`break` leaves the innermost enclosing `while` loop and `continue` starts
+its next iteration, so both are only allowed inside the body of a `while`.
+A loop in a caller does not count. To leave a function early, use
+`return`:
+
+    defun first () : integer {
+        if true {
+            return 1
+        }
+        0
+    }"
+            }
+            ErrorCode::InvalidFloatLiteral => {
+                "A float literal is malformed.
+
+Erroneous code example:
+
+    a : f64 = 1.5e
+    b : f64 = 2.5u8
+    c : f64 = 1e309
+
+A float literal has a fraction, an exponent or both, as in `3.14`, `.5`
+and `1e-9`, with `_` allowed between two digits. It may end with the suffix
+`f32` or `f64`, and no literal may be too large to be a finite `f64`:
+
+    a : f64 = 1.5e0
+    b : f64 = 2.5f64
+    c : f64 = 1e308"
+            }
+            ErrorCode::UnterminatedString => {
+                "A string or character literal was never closed.
+
+Erroneous code example:
+
+    greeting : string = \"hello
+    initial : char = 'h
+
+A string literal runs from one `\"` to the next, and may span lines; a
+character literal must be closed by a `'` on the same line. A quote
+preceded by a backslash, as in `\\\"`, is part of the literal rather than
+its end. Add the closing quote:
+
+    greeting : string = \"hello\"
+    initial : char = 'h'"
+            }
+            ErrorCode::InvalidEscape => {
+                "A string or character literal contains an invalid escape sequence.
+
+Erroneous code example:
+
+    path : string = \"C:\\data\"
+    snowman : char = '\\u{2603'
+
+The escapes are `\\n` (newline), `\\t` (tab), `\\r` (carriage return), `\\0`
+(the null character), `\\\\` (a backslash), `\\\"` and `\\'` (quotes), and
+`\\u{...}` with one to six hexadecimal digits naming a Unicode scalar value.
+To write a backslash itself, escape it:
+
+    path : string = \"C:\\\\data\"
+    snowman : char = '\\u{2603}'"
+            }
+            ErrorCode::InvalidCharLiteral => {
+                "A character literal does not contain exactly one character.
+
+Erroneous code example:
+
+    letter : char = 'ab'
+    nothing : char = ''
+
+A `char` holds a single Unicode scalar value, written between single
+quotes. Text of any length is a string, written between double quotes:
+
+    letter : char = 'a'
+    word : string = \"ab\""
+            }
+            ErrorCode::UnknownField => {
+                "A field was used that its struct does not have.
+
+Erroneous code example:
+
+    struct Point { x : integer, y : integer }
+
+    p : Point = Point { x: 1, z: 2 }
+    a : integer = p.z
+
+Only the fields listed in the struct definition can be initialized, read
+or assigned, and values of other types have no fields at all. Check the
+spelling of the field name:
+
+    p : Point = Point { x: 1, y: 2 }
+    a : integer = p.y"
+            }
+            ErrorCode::MissingField => {
+                "A struct literal leaves out some of the struct's fields.
+
+Erroneous code example:
+
+    struct Point { x : integer, y : integer }
+
+    p : Point = Point { x: 1 }
+
+Every field must be given a value, in any order. A variable of a struct
+type that is declared without an initializer starts with every field set
+to zero instead:
+
+    p : Point = Point { y: 0, x: 1 }
+    q : Point"
+            }
+            ErrorCode::TrailingComma => {
+                "A parameter or argument list ends with a comma.
+
+Erroneous code example:
+
+    defun add (a : integer, b : integer,) : integer { a + b }
+    c : integer = add(1, 2,)
+
+Commas go only between the items of a list. Remove the last one:
+
+    defun add (a : integer, b : integer) : integer { a + b }
+    c : integer = add(1, 2)"
+            }
+            ErrorCode::InvalidAssignmentTarget => {
+                "The left-hand side of `:=` is not something that can be assigned to.
+
+Erroneous code example:
+
+    struct Point { x : integer, y : integer }
+
+    Point { x: 1, y: 2 }.x := 3
+
+Only a variable, or a field of a variable or of one of its fields, as in
+`p.x` or `line.start.x`, can be assigned. Store the value in a variable
+first:
+
+    p : Point = Point { x: 1, y: 2 }
+    p.x := 3"
+            }
+            ErrorCode::IntegerOutOfRange => {
+                "An integer literal does not fit in its type.
+
+Erroneous code example:
+
+    a : u8 = 256
+    b : i8 = 200i8
+    c : u8 = -1
+
+An unsuffixed literal takes its type from where it is used, and `i64`
+when nothing decides it; a suffixed one has the type of its suffix.
+Either way, the value must lie within the range of that type, which for
+`u8` is 0 to 255 and for `i8` is -128 to 127. Use a value in range, or a
+wider type:
+
+    a : u16 = 256
+    b : i16 = 200i16
+    c : i8 = -1"
+            }
+            ErrorCode::FloatOutOfRange => {
+                "A float literal is too large for its type.
+
+Erroneous code example:
+
+    a : f32 = 1e39
+
+An unsuffixed literal takes its type from where it is used, and `f64`
+when nothing decides it. The value must be finite in that type, and the
+largest `f32` is about 3.4e38. Use a smaller value, or `f64`:
+
+    a : f64 = 1e39"
+            }
+            ErrorCode::UnsignedNegation => {
+                "A value of an unsigned integer type was negated.
+
+Erroneous code example:
+
+    a : u8 = 5
+    b : u8 = -a
+
+Unsigned types have no negative values. Use a signed type, or subtract
+from a larger value instead:
+
+    a : i16 = 5
+    b : i16 = -a"
+            }
+            ErrorCode::InvalidCast => {
+                "An `as` cast converts between types that have no conversion.
+
+Erroneous code example:
+
+    a : integer = \"42\" as integer
+    b : boolean = 1 as boolean
+
+Casts convert between any integer and float types, and from `boolean` or
+`char` to an integer type. A value may also be cast to its own type.
+Compare with zero to get a `boolean` from a number:
+
+    b : boolean = 1 != 0"
+            }
+            ErrorCode::NestedDefinition => {
+                "A function or struct was defined inside a block.
+
+Erroneous code example:
+
+    defun main () : integer {
+        defun five () : integer { 5 }
+        five()
+    }
+
+Functions and structs may only be defined at the top level of a file.
+Move the definition out of the block; a function may be called before
+its definition:
+
+    defun five () : integer { 5 }
+
+    defun main () : integer {
+        five()
+    }"
+            }
+            ErrorCode::InvalidMain => {
+                "The `main` function has a signature it cannot be run with.
+
+Erroneous code example:
+
+    defun main (n : integer) : f64 { 1.5 }
+
+When a program is run, `main` is called with no arguments and its result
+becomes the exit status of the program. So `main` must not take any
+parameters, and must return an integer type, whose value must be from 0
+to 255, or `void`, which exits with 0:
+
+    defun main () : integer { 1 }"
+            }
+            ErrorCode::UnreadableFile => {
+                "A source file named on the command line could not be read.
+
+The message gives the reason reported by the operating system, such as a
+file that does not exist, a directory, a file without read permission, or
+one that is not valid UTF-8. Check the path, and that the file is a text
+file:
+
+    compiler --run ./example"
+            }
+            ErrorCode::InvalidExitStatus => {
+                "With `--run`, `main` returned a value that is not an exit status.
+
+Erroneous code example:
+
+    defun main () : integer {
+        return 256
+    }
+
+An exit status is a single byte, from 0 to 255. Rather than cut the
+value down to fit, which could turn a failure into a success, the run is
+reported as failed. Return a value in range:
+
+    defun main () : integer {
+        return 1
+    }"
+            }
+            ErrorCode::Unsupported => {
+                "The program uses a construct that is not implemented yet.
+
+The language is still growing, and some forms that parse cannot be
+compiled or run yet. The message names the construct in question."
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `code` is the last variant of `ErrorCode`. The match is
+    /// exhaustive, so a new variant does not compile until it is placed
+    /// here, as a reminder to add it to `ALL_ERROR_CODES` too.
+    fn is_last(code: ErrorCode) -> bool {
+        match code {
+            ErrorCode::InvalidExitStatus => true,
+            ErrorCode::UnknownType
+            | ErrorCode::UnexpectedCharacter
+            | ErrorCode::UnterminatedBlockComment
+            | ErrorCode::UnexpectedToken
+            | ErrorCode::InvalidIntegerLiteral
+            | ErrorCode::UnterminatedBlock
+            | ErrorCode::UnmatchedBrace
+            | ErrorCode::ExpectedTopLevelForm
+            | ErrorCode::LexPastEnd
+            | ErrorCode::UndefinedFunction
+            | ErrorCode::ArgumentCount
+            | ErrorCode::UndefinedVariable
+            | ErrorCode::ArithmeticOverflow
+            | ErrorCode::DivisionByZero
+            | ErrorCode::Unsupported
+            | ErrorCode::DuplicateDeclaration
+            | ErrorCode::DuplicateParameter
+            | ErrorCode::TypeMismatch
+            | ErrorCode::VoidValue
+            | ErrorCode::BreakOutsideLoop
+            | ErrorCode::InvalidFloatLiteral
+            | ErrorCode::UnterminatedString
+            | ErrorCode::InvalidEscape
+            | ErrorCode::InvalidCharLiteral
+            | ErrorCode::UnknownField
+            | ErrorCode::MissingField
+            | ErrorCode::TrailingComma
+            | ErrorCode::InvalidAssignmentTarget
+            | ErrorCode::IntegerOutOfRange
+            | ErrorCode::FloatOutOfRange
+            | ErrorCode::UnsignedNegation
+            | ErrorCode::InvalidCast
+            | ErrorCode::NestedDefinition
+            | ErrorCode::InvalidMain
+            | ErrorCode::UnreadableFile => false,
+        }
+    }
+
+    #[test]
+    fn every_code_is_listed_once_in_order() {
+        for (position, code) in ALL_ERROR_CODES.iter().enumerate() {
+            assert_eq!(*code as usize, position, "{:?} is out of place", code);
+            assert_eq!(code.code(), format!("CL{:04}", position + 1));
+        }
+        let last = ALL_ERROR_CODES.last().copied().unwrap();
+        assert!(is_last(last), "{:?} is not the last variant", last);
+    }
+
+    #[test]
+    fn every_code_round_trips() {
+        for code in ALL_ERROR_CODES {
+            assert_eq!(ErrorCode::from_code(code.code()), Some(*code));
+            assert!(!code.explanation().is_empty());
+        }
+        assert_eq!(ErrorCode::from_code("CL0000"), None);
+        assert_eq!(ErrorCode::from_code("cl0001"), None);
+    }
+}
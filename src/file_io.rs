@@ -3,15 +3,19 @@ use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
 
 fn file_size(file: &mut File) -> io::Result<u64> {
-    let original_pos = file.seek(SeekFrom::Current(0))?;
+    let original_pos = file.stream_position()?;
     let size = file.seek(SeekFrom::End(0))?;
     file.seek(SeekFrom::Start(original_pos))?;
     Ok(size)
 }
 
-fn file_contents(path: &str) -> io::Result<String> {
+pub fn file_contents(path: &str) -> io::Result<String> {
     let path = Path::new(path);
-    let mut file = File::open(&path)?;
+    let mut file = File::open(path)?;
+    // A directory opens, but seeking to its end gives a meaningless size.
+    if file.metadata()?.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::IsADirectory, "is a directory"));
+    }
     let size = file_size(&mut file)?;
 
     let mut contents = String::with_capacity(size as usize);
@@ -19,10 +23,17 @@ fn file_contents(path: &str) -> io::Result<String> {
     Ok(contents)
 }
 
-fn main() {
-    match file_contents("example.txt") {
-        Ok(contents) => println!("File contents: {}", contents),
-        Err(e) => eprintln!("Failed to read file: {}", e),
-    }
+pub fn stdin_contents() -> io::Result<String> {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    Ok(contents)
 }
 
+/// Read the source at `path`, where `-` means standard input.
+pub fn source_contents(path: &str) -> io::Result<String> {
+    if path == "-" {
+        stdin_contents()
+    } else {
+        file_contents(path)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::interp::{binary_value, cast_value, unary_value, value_type, Value};
+use crate::node::{Node, NodeValue};
+
+/// Replace every operation on literals in a typed `Program` with the
+/// literal it evaluates to. Folding computes exactly as the interpreter
+/// does, so integers at the width of their type and floats by IEEE 754,
+/// where `1.0 / 0.0` is an infinity. An operation that would fail when run,
+/// such as an integer overflow or a division by zero, is left in place to
+/// fail then.
+pub fn fold_constants(program: &mut Node) {
+    for form in program.children.iter_mut() {
+        let mut form = Some(form);
+        while let Some(node) = form {
+            fold(node);
+            form = node.next_child.as_deref_mut();
+        }
+    }
+}
+
+fn fold(node: &mut Node) {
+    for child in node.children.iter_mut() {
+        fold(child);
+    }
+    let type_name = value_type(node).to_string();
+    let span = node.span;
+    let folded = match &mut node.value {
+        Some(NodeValue::VariableAssignment { value, .. }) => {
+            fold(value);
+            None
+        }
+        Some(NodeValue::FunctionDefinition { body, .. }) => {
+            body.iter_mut().for_each(fold);
+            None
+        }
+        Some(NodeValue::FunctionCall { args, .. }) => {
+            args.iter_mut().for_each(fold);
+            None
+        }
+        Some(NodeValue::StructLiteral { fields, .. }) => {
+            fields.iter_mut().for_each(|field| fold(&mut field.value));
+            None
+        }
+        Some(NodeValue::FieldAccess { value, .. }) => {
+            fold(value);
+            None
+        }
+        Some(NodeValue::FieldAssignment { value, .. }) => {
+            fold(value);
+            None
+        }
+        Some(NodeValue::Return { value: Some(value) }) => {
+            fold(value);
+            None
+        }
+        Some(NodeValue::If { condition, then_body, else_body }) => {
+            fold(condition);
+            then_body.iter_mut().for_each(fold);
+            else_body.iter_mut().flatten().for_each(fold);
+            None
+        }
+        Some(NodeValue::While { condition, body }) => {
+            fold(condition);
+            body.iter_mut().for_each(fold);
+            None
+        }
+        Some(NodeValue::UnaryOp { operator, operand }) => {
+            fold(operand);
+            Value::from_literal(operand).and_then(|value| unary_value(*operator, &value, &type_name, span).ok())
+        }
+        Some(NodeValue::BinaryOp { operator, lhs, rhs }) => {
+            fold(lhs);
+            fold(rhs);
+            match (Value::from_literal(lhs), Value::from_literal(rhs)) {
+                (Some(lhs_value), Some(rhs_value)) => {
+                    binary_value(*operator, &lhs_value, &rhs_value, value_type(lhs), span).ok()
+                }
+                _ => None,
+            }
+        }
+        Some(NodeValue::Cast { value, target }) => {
+            fold(value);
+            Value::from_literal(value).map(|value| cast_value(&value, target))
+        }
+        _ => None,
+    };
+    if let Some(value) = folded {
+        let literal = value.to_literal(&type_name);
+        node.node_type = literal.node_type.clone();
+        node.value = literal.value.clone();
+    }
+}
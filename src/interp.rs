@@ -0,0 +1,697 @@
+use crate::environment::Environment;
+use crate::error::Diagnostic;
+use crate::error_codes::ErrorCode;
+use crate::node::{BinaryOperator, FieldInit, Node, NodeType, NodeValue, UnaryOperator};
+use crate::span::Span;
+use crate::typechecker::{float_type, integer_range, integer_type, BOOLEAN, CHAR, DEFAULT_INTEGER, STRING, VOID};
+
+/// A value at run time. Booleans are the integers 1 and 0, and characters
+/// are their Unicode scalar values. A struct holds its fields in the order
+/// of its definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Struct { name: String, fields: Vec<(String, Value)> },
+}
+
+impl Value {
+    /// The value of `node` if it is a literal.
+    pub fn from_literal(node: &Node) -> Option<Value> {
+        if let Some(NodeValue::StructLiteral { name, fields }) = &node.value {
+            let fields = fields
+                .iter()
+                .map(|field| Some((field.name.clone(), Value::from_literal(&field.value)?)))
+                .collect::<Option<_>>()?;
+            return Some(Value::Struct { name: name.clone(), fields });
+        }
+        match node.value {
+            Some(NodeValue::Integer(value)) => Some(Value::Integer(value)),
+            Some(NodeValue::Boolean(value)) => Some(Value::Integer(value as i128)),
+            Some(NodeValue::Float(value)) => Some(Value::Float(value)),
+            Some(NodeValue::Char(value)) => Some(Value::Integer(value as i128)),
+            Some(NodeValue::String(ref value)) => Some(Value::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    /// A literal node holding this value as the type `type_name`. The
+    /// fields of a struct keep only their values, so a boolean or
+    /// character field becomes an integer literal.
+    pub fn to_literal(&self, type_name: &str) -> Node {
+        match *self {
+            Value::Integer(value) if type_name == BOOLEAN => Node::from_boolean(value != 0),
+            Value::Integer(value) if type_name == CHAR => {
+                Node::from_char(u32::try_from(value).ok().and_then(char::from_u32).unwrap_or_default())
+            }
+            Value::Integer(value) => Node::from_integer(value),
+            Value::Float(value) => Node::from_float(value),
+            Value::String(ref value) => Node::from_string(value),
+            Value::Struct { ref name, ref fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| FieldInit { name: field.clone(), value: value.to_literal(""), span: Span::default() })
+                    .collect();
+                Node::new(NodeType::StructLiteral, Some(NodeValue::StructLiteral { name: name.clone(), fields }))
+            }
+        }
+    }
+
+    /// The value as an integer, truncating a float. A string or struct has
+    /// none, and gives 0.
+    pub fn as_integer(&self) -> i128 {
+        match *self {
+            Value::Integer(value) => value,
+            Value::Float(value) => value as i128,
+            Value::String(_) | Value::Struct { .. } => 0,
+        }
+    }
+
+    fn is_true(&self) -> bool {
+        *self != Value::Integer(0)
+    }
+
+    /// The field `path` of this struct, or of a struct within it.
+    fn field_mut(&mut self, path: &[String]) -> Option<&mut Value> {
+        path.iter().try_fold(self, |value, field| match value {
+            Value::Struct { fields, .. } => fields.iter_mut().find(|(name, _)| name == field).map(|(_, value)| value),
+            _ => None,
+        })
+    }
+}
+
+/// A jump out of the statements being executed, pending until the loop or
+/// call it targets has unwound to it.
+enum Unwind {
+    Return(Value),
+    Break,
+    Continue,
+}
+
+/// A tree-walking interpreter over a typed `Program`. Variables hold
+/// literal nodes; a function evaluates to the value of its `return`, or
+/// else of its last statement.
+///
+/// Arithmetic is done at the type the checker gave each expression. An
+/// integer result that does not fit traps, except that a left shift
+/// discards the bits shifted out; floats follow IEEE 754, so overflow
+/// gives an infinity and `0.0 / 0.0` a NaN.
+///
+/// Scoping is lexical: a function body sees its own scopes in `locals`,
+/// then `globals`, but never the locals of its caller.
+pub struct Interpreter {
+    pub globals: Environment,
+    pub functions: Environment,
+    pub structs: Environment,
+    locals: Option<Environment>,
+    unwinding: Option<Unwind>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            globals: Environment::new(None),
+            functions: Environment::new(None),
+            structs: Environment::new(None),
+            locals: None,
+            unwinding: None,
+        }
+    }
+
+    /// Run every top-level form of `program` in order, then call `main` if
+    /// it is defined. The result of `main` is the program's exit value, or
+    /// 0 if it returns `void`.
+    /// Every function is defined before any form runs, so that an
+    /// initializer may call a function defined after it.
+    pub fn run_program(&mut self, program: &Node) -> Result<Value, Diagnostic> {
+        for form in program.iter_children() {
+            if let Some(NodeValue::FunctionDefinition { name, .. }) = &form.value {
+                self.functions.set(&Node::from_symbol(name), form.clone()).unwrap();
+            }
+        }
+        for form in program.iter_children() {
+            self.execute(form)?;
+        }
+        match self.functions.get(&Node::from_symbol("main")).map(|main| &main.value) {
+            Some(Some(NodeValue::FunctionDefinition { return_type, .. })) if return_type == VOID => {
+                self.call_function("main", &[], program.span)?;
+                Ok(Value::Integer(0))
+            }
+            Some(_) => self.call_function("main", &[], program.span),
+            None => Ok(Value::Integer(0)),
+        }
+    }
+
+    pub fn call_function(&mut self, name: &str, args: &[Value], span: Span) -> Result<Value, Diagnostic> {
+        let Some(function) = self.functions.get(&Node::from_symbol(name)).cloned() else {
+            return Err(Diagnostic::error(
+                ErrorCode::UndefinedFunction,
+                span,
+                format!("Call to undefined function: {}", name),
+            ));
+        };
+        let Some(NodeValue::FunctionDefinition { params, body, .. }) = &function.value else {
+            unreachable!("functions only holds function definitions");
+        };
+        if params.len() != args.len() {
+            return Err(Diagnostic::error(
+                ErrorCode::ArgumentCount,
+                span,
+                format!("{} takes {} argument(s) but {} were given", name, params.len(), args.len()),
+            )
+            .with_label(function.span, "function defined here"));
+        }
+
+        let mut locals = Environment::new(None);
+        for (param, value) in params.iter().zip(args) {
+            locals.set(&Node::from_symbol(&param.name), value.to_literal(&param.param_type)).unwrap();
+        }
+        // The body gets its own scope so that its declarations may shadow
+        // the parameters.
+        locals.push_scope();
+        let caller_locals = self.locals.replace(locals);
+        let mut result = self.execute_statements(body);
+        if let Some(Unwind::Return(value)) = self.unwinding.take() {
+            result = Ok(value);
+        }
+        self.locals = caller_locals;
+        result
+    }
+
+    /// Execute `body` in order, yielding the value of the last statement,
+    /// and stopping early on an error or a jump.
+    fn execute_statements(&mut self, body: &[Node]) -> Result<Value, Diagnostic> {
+        let mut value = Value::Integer(0);
+        for stmt in body {
+            value = self.execute(stmt)?;
+            if self.unwinding.is_some() {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Execute `body` in a scope of its own.
+    fn execute_block(&mut self, body: &[Node]) -> Result<Value, Diagnostic> {
+        self.scope().push_scope();
+        let result = self.execute_statements(body);
+        self.scope().pop_scope();
+        result
+    }
+
+    fn scope(&mut self) -> &mut Environment {
+        self.locals.as_mut().unwrap_or(&mut self.globals)
+    }
+
+    /// The value a variable of `type_name` holds before it is assigned: zero,
+    /// the empty string, or a struct of such values.
+    fn zero(&self, type_name: &str) -> Value {
+        if let Some(Some(NodeValue::StructDefinition { name, fields })) =
+            self.structs.get(&Node::from_symbol(type_name)).map(|definition| &definition.value)
+        {
+            let fields = fields.iter().map(|field| (field.name.clone(), self.zero(&field.field_type))).collect();
+            return Value::Struct { name: name.clone(), fields };
+        }
+        match float_type(type_name) {
+            Some(_) => Value::Float(0.0),
+            None if type_name == STRING => Value::String(String::new()),
+            None => Value::Integer(0),
+        }
+    }
+
+    /// Store `literal` in the variable `name`, in the innermost scope that
+    /// declares it.
+    fn assign(&mut self, name: &str, literal: Node, span: Span) -> Result<(), Diagnostic> {
+        let id = Node::from_symbol(name);
+        let assigned = match self.locals.as_mut() {
+            Some(locals) if locals.get(&id).is_some() => locals.assign(&id, literal),
+            _ => self.globals.assign(&id, literal),
+        };
+        assigned.map_err(|_| {
+            Diagnostic::error(
+                ErrorCode::UndefinedVariable,
+                span,
+                format!("Assignment to undefined variable: {}", name),
+            )
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        let id = Node::from_symbol(name);
+        let value = match self.locals.as_ref().and_then(|locals| locals.get(&id)) {
+            Some(value) => value,
+            None => self.globals.get(&id)?,
+        };
+        Value::from_literal(value)
+    }
+
+    /// Execute a statement, yielding its value.
+    fn execute(&mut self, node: &Node) -> Result<Value, Diagnostic> {
+        match &node.value {
+            Some(NodeValue::FunctionDefinition { name, .. }) => {
+                self.functions.set(&Node::from_symbol(name), node.clone()).unwrap();
+                Ok(Value::Integer(0))
+            }
+            Some(NodeValue::StructDefinition { name, .. }) => {
+                self.structs.set(&Node::from_symbol(name), node.clone()).unwrap();
+                Ok(Value::Integer(0))
+            }
+            Some(NodeValue::VariableDeclaration { name, var_type }) => {
+                let value = match node.iter_children().next() {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => self.zero(var_type),
+                };
+                self.scope().set(&Node::from_symbol(name), value.to_literal(var_type)).unwrap();
+                Ok(value)
+            }
+            Some(NodeValue::VariableAssignment { name, value }) => {
+                let literal_type = value_type(value).to_string();
+                let value = self.evaluate(value)?;
+                self.assign(name, value.to_literal(&literal_type), node.span)?;
+                Ok(value)
+            }
+            // The variable is updated as a whole, with the field replaced.
+            Some(NodeValue::FieldAssignment { target, value }) => {
+                let value = self.evaluate(value)?;
+                let mut path = Vec::new();
+                let mut root = target.as_ref();
+                while let Some(NodeValue::FieldAccess { value, field }) = &root.value {
+                    path.insert(0, field.clone());
+                    root = value;
+                }
+                let Some(NodeValue::Symbol(name)) = &root.value else {
+                    unreachable!("the parser only accepts fields of variables as assignment targets");
+                };
+                let mut whole = self.evaluate(root)?;
+                *whole.field_mut(&path).expect("the type checker validated every field") = value.clone();
+                self.assign(name, whole.to_literal(value_type(root)), node.span)?;
+                Ok(value)
+            }
+            Some(NodeValue::Return { value }) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Integer(0),
+                };
+                self.unwinding = Some(Unwind::Return(value.clone()));
+                Ok(value)
+            }
+            Some(NodeValue::If { condition, then_body, else_body }) => {
+                if self.evaluate(condition)?.is_true() {
+                    self.execute_block(then_body)
+                } else if let Some(else_body) = else_body {
+                    self.execute_block(else_body)
+                } else {
+                    Ok(Value::Integer(0))
+                }
+            }
+            Some(NodeValue::While { condition, body }) => {
+                while self.evaluate(condition)?.is_true() {
+                    self.execute_block(body)?;
+                    match self.unwinding {
+                        Some(Unwind::Break) => {
+                            self.unwinding = None;
+                            break;
+                        }
+                        Some(Unwind::Continue) => self.unwinding = None,
+                        Some(Unwind::Return(_)) => break,
+                        None => {}
+                    }
+                }
+                Ok(Value::Integer(0))
+            }
+            None if node.node_type == NodeType::Break => {
+                self.unwinding = Some(Unwind::Break);
+                Ok(Value::Integer(0))
+            }
+            None if node.node_type == NodeType::Continue => {
+                self.unwinding = Some(Unwind::Continue);
+                Ok(Value::Integer(0))
+            }
+            _ => self.evaluate(node),
+        }
+    }
+
+    fn evaluate(&mut self, node: &Node) -> Result<Value, Diagnostic> {
+        if let Some(value) = Value::from_literal(node) {
+            return Ok(value);
+        }
+        match &node.value {
+            Some(NodeValue::Symbol(name)) => self.lookup(name).ok_or_else(|| {
+                Diagnostic::error(
+                    ErrorCode::UndefinedVariable,
+                    node.span,
+                    format!("Use of undefined variable: {}", name),
+                )
+            }),
+            Some(NodeValue::FunctionCall { name, args }) => {
+                let args = args.iter().map(|arg| self.evaluate(arg)).collect::<Result<Vec<_>, _>>()?;
+                self.call_function(name, &args, node.span)
+            }
+            Some(NodeValue::UnaryOp { operator, operand }) => {
+                let value = self.evaluate(operand)?;
+                unary_value(*operator, &value, value_type(node), node.span)
+            }
+            Some(NodeValue::BinaryOp { operator, lhs, rhs }) => {
+                let lhs_value = self.evaluate(lhs)?;
+                match operator {
+                    BinaryOperator::LogicalAnd if !lhs_value.is_true() => return Ok(Value::Integer(0)),
+                    BinaryOperator::LogicalOr if lhs_value.is_true() => return Ok(Value::Integer(1)),
+                    _ => {}
+                }
+                let rhs_value = self.evaluate(rhs)?;
+                binary_value(*operator, &lhs_value, &rhs_value, value_type(lhs), node.span)
+            }
+            Some(NodeValue::Cast { value, target }) => Ok(cast_value(&self.evaluate(value)?, target)),
+            Some(NodeValue::StructLiteral { name, fields }) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), self.evaluate(&field.value)?)))
+                    .collect::<Result<_, Diagnostic>>()?;
+                Ok(Value::Struct { name: name.clone(), fields })
+            }
+            Some(NodeValue::FieldAccess { value, field }) => match self.evaluate(value)? {
+                Value::Struct { fields, .. } => {
+                    Ok(fields.into_iter().find(|(name, _)| name == field).expect("the type checker validated every field").1)
+                }
+                _ => unreachable!("the type checker only allows fields of structs"),
+            },
+            _ if node.node_type == NodeType::None => Ok(Value::Integer(0)),
+            _ => Err(Diagnostic::error(
+                ErrorCode::Unsupported,
+                node.span,
+                format!("Cannot evaluate: {}", node),
+            )),
+        }
+    }
+}
+
+/// The type `node` was checked to have.
+pub fn value_type(node: &Node) -> &str {
+    node.type_name.as_deref().unwrap_or(DEFAULT_INTEGER)
+}
+
+fn fits(value: i128, type_name: &str) -> bool {
+    integer_range(type_name).is_some_and(|(min, max)| (min..=max).contains(&value))
+}
+
+/// Keep only the low bits of `value` that fit in `type_name`, as two's
+/// complement.
+fn wrap(value: i128, type_name: &str) -> i128 {
+    let (bits, signed) = integer_type(type_name).unwrap_or((64, true));
+    let value = value & ((1 << bits) - 1);
+    if signed && value >= 1 << (bits - 1) {
+        value - (1 << bits)
+    } else {
+        value
+    }
+}
+
+/// Round `value` to the precision of the float type `type_name`.
+fn round_float(value: f64, type_name: &str) -> f64 {
+    match float_type(type_name) {
+        Some(32) => value as f32 as f64,
+        _ => value,
+    }
+}
+
+fn overflow(span: Span) -> Diagnostic {
+    Diagnostic::error(ErrorCode::ArithmeticOverflow, span, "Arithmetic overflow")
+}
+
+/// Apply `operator` to an operand of type `type_name`, which is also the
+/// type of the result.
+pub fn unary_value(operator: UnaryOperator, value: &Value, type_name: &str, span: Span) -> Result<Value, Diagnostic> {
+    match (operator, value) {
+        (UnaryOperator::Negate, Value::Integer(value)) => value
+            .checked_neg()
+            .filter(|value| fits(*value, type_name))
+            .map(Value::Integer)
+            .ok_or_else(|| overflow(span)),
+        (UnaryOperator::Negate, Value::Float(value)) => Ok(Value::Float(-value)),
+        (UnaryOperator::Negate, _) => Err(Diagnostic::error(ErrorCode::Unsupported, span, "Cannot negate this value")),
+        (UnaryOperator::Not, value) => Ok(Value::Integer(!value.is_true() as i128)),
+    }
+}
+
+/// Apply `operator` to operands of type `type_name`, which is also the type
+/// of an arithmetic result.
+pub fn binary_value(operator: BinaryOperator, lhs: &Value, rhs: &Value, type_name: &str, span: Span) -> Result<Value, Diagnostic> {
+    match (lhs, rhs) {
+        (Value::Integer(lhs), Value::Integer(rhs)) => integer_binary(operator, *lhs, *rhs, type_name, span),
+        (Value::Float(lhs), Value::Float(rhs)) => Ok(float_binary(operator, *lhs, *rhs, type_name)),
+        _ if operator == BinaryOperator::Equal => Ok(Value::Integer((lhs == rhs) as i128)),
+        _ if operator == BinaryOperator::NotEqual => Ok(Value::Integer((lhs != rhs) as i128)),
+        _ => Err(Diagnostic::error(
+            ErrorCode::Unsupported,
+            span,
+            format!("Cannot apply {} to these operands", operator.symbol()),
+        )),
+    }
+}
+
+fn integer_binary(operator: BinaryOperator, lhs: i128, rhs: i128, type_name: &str, span: Span) -> Result<Value, Diagnostic> {
+    if matches!(operator, BinaryOperator::Divide | BinaryOperator::Modulo) && rhs == 0 {
+        return Err(Diagnostic::error(ErrorCode::DivisionByZero, span, "Division by zero"));
+    }
+    let (bits, _) = integer_type(type_name).unwrap_or((64, true));
+    let shift = u32::try_from(rhs).ok().filter(|rhs| *rhs < bits);
+    let value = match operator {
+        BinaryOperator::Add => lhs.checked_add(rhs),
+        BinaryOperator::Subtract => lhs.checked_sub(rhs),
+        BinaryOperator::Multiply => lhs.checked_mul(rhs),
+        BinaryOperator::Divide => lhs.checked_div(rhs),
+        BinaryOperator::Modulo => lhs.checked_rem(rhs),
+        BinaryOperator::ShiftLeft => shift.map(|rhs| wrap(((lhs as u128) << rhs) as i128, type_name)),
+        BinaryOperator::ShiftRight => shift.map(|rhs| lhs >> rhs),
+        BinaryOperator::BitAnd => Some(lhs & rhs),
+        BinaryOperator::BitOr => Some(lhs | rhs),
+        BinaryOperator::BitXor => Some(lhs ^ rhs),
+        BinaryOperator::Equal => return Ok(Value::Integer((lhs == rhs) as i128)),
+        BinaryOperator::NotEqual => return Ok(Value::Integer((lhs != rhs) as i128)),
+        BinaryOperator::Less => return Ok(Value::Integer((lhs < rhs) as i128)),
+        BinaryOperator::LessEqual => return Ok(Value::Integer((lhs <= rhs) as i128)),
+        BinaryOperator::Greater => return Ok(Value::Integer((lhs > rhs) as i128)),
+        BinaryOperator::GreaterEqual => return Ok(Value::Integer((lhs >= rhs) as i128)),
+        BinaryOperator::LogicalAnd => return Ok(Value::Integer((lhs != 0 && rhs != 0) as i128)),
+        BinaryOperator::LogicalOr => return Ok(Value::Integer((lhs != 0 || rhs != 0) as i128)),
+    };
+    value.filter(|value| fits(*value, type_name)).map(Value::Integer).ok_or_else(|| overflow(span))
+}
+
+/// IEEE 754 arithmetic. An `f32` operation is done in `f64` and rounded,
+/// which gives the same result as doing it in `f32`.
+fn float_binary(operator: BinaryOperator, lhs: f64, rhs: f64, type_name: &str) -> Value {
+    let value = match operator {
+        BinaryOperator::Add => lhs + rhs,
+        BinaryOperator::Subtract => lhs - rhs,
+        BinaryOperator::Multiply => lhs * rhs,
+        BinaryOperator::Divide => lhs / rhs,
+        BinaryOperator::Modulo => lhs % rhs,
+        BinaryOperator::Equal => return Value::Integer((lhs == rhs) as i128),
+        BinaryOperator::NotEqual => return Value::Integer((lhs != rhs) as i128),
+        BinaryOperator::Less => return Value::Integer((lhs < rhs) as i128),
+        BinaryOperator::LessEqual => return Value::Integer((lhs <= rhs) as i128),
+        BinaryOperator::Greater => return Value::Integer((lhs > rhs) as i128),
+        BinaryOperator::GreaterEqual => return Value::Integer((lhs >= rhs) as i128),
+        _ => unreachable!("{} does not take floats", operator.symbol()),
+    };
+    Value::Float(round_float(value, type_name))
+}
+
+/// Convert `value` to `target`. An integer wraps to a narrower integer
+/// type; a float truncates towards zero to an integer, saturating at the
+/// bounds of the type, with NaN giving 0.
+pub fn cast_value(value: &Value, target: &str) -> Value {
+    match (value.clone(), float_type(target)) {
+        (value @ (Value::String(_) | Value::Struct { .. }), _) => value,
+        (Value::Integer(value), Some(32)) => Value::Float(value as f32 as f64),
+        (Value::Integer(value), Some(_)) => Value::Float(value as f64),
+        (Value::Integer(value), None) => Value::Integer(wrap(value, target)),
+        (Value::Float(value), Some(_)) => Value::Float(round_float(value, target)),
+        (Value::Float(value), None) => {
+            let (min, max) = integer_range(target).unwrap_or((i64::MIN as i128, i64::MAX as i128));
+            Value::Integer(if value.is_nan() {
+                0
+            } else if value <= min as f64 {
+                min
+            } else if value >= max as f64 {
+                max
+            } else {
+                value as i128
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::compile;
+
+    /// An interpreter that has run `source`, which must compile cleanly,
+    /// and the result of its `main`.
+    fn run(source: &str) -> (Interpreter, Result<Value, Diagnostic>) {
+        let (program, diagnostics) = compile(source, 0);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.run_program(&program);
+        (interpreter, result)
+    }
+
+    fn trap(source: &str) -> Diagnostic {
+        run(source).1.expect_err("the program should trap")
+    }
+
+    #[test]
+    fn calls_a_function_with_arguments() {
+        let (mut interpreter, _) = run("defun add (a:integer, b:integer):integer { a + b }");
+        let sum = interpreter.call_function("add", &[Value::Integer(40), Value::Integer(2)], Span::default());
+        assert_eq!(sum.unwrap(), Value::Integer(42));
+    }
+
+    #[test]
+    fn checks_the_callee_and_argument_count() {
+        let (mut interpreter, _) = run("defun one ():integer { 1 }");
+        let err = interpreter.call_function("two", &[], Span::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::UndefinedFunction);
+        let err = interpreter.call_function("one", &[Value::Integer(1)], Span::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::ArgumentCount);
+    }
+
+    #[test]
+    fn the_caller_keeps_its_locals() {
+        let (mut interpreter, _) = run(
+            "defun inner (x:integer):integer { x := x + 1\n x }
+             defun outer (x:integer):integer { inner(x * 10) + x }",
+        );
+        let value = interpreter.call_function("outer", &[Value::Integer(3)], Span::default());
+        assert_eq!(value.unwrap(), Value::Integer(34));
+    }
+
+    #[test]
+    fn an_initializer_may_call_a_later_function() {
+        let (_, result) = run(
+            "x : integer = f()
+             struct P { a : integer }
+             p : P = mk()
+             defun f ():integer { 3 }
+             defun mk ():P { P { a: 4 } }
+             defun main ():integer { x + p.a }",
+        );
+        assert_eq!(result.unwrap(), Value::Integer(7));
+    }
+
+    #[test]
+    fn a_void_main_exits_with_0() {
+        assert_eq!(run("defun main () { x : integer = 7 }").1.unwrap(), Value::Integer(0));
+        assert_eq!(run("defun main () { return }").1.unwrap(), Value::Integer(0));
+    }
+
+    #[test]
+    fn a_struct_variable_starts_with_every_field_zero() {
+        let source = "struct I { v : integer, f : f64, b : boolean }
+             struct O { i : I, w : u8 }
+             defun main () : integer {
+                 o : O
+                 if o.i.b || o.i.f != 0.0 || o.w != 0 { return 1 }
+                 o.i.v := o.i.v + 40
+                 o.w := 2
+                 o.i.v + o.w as integer
+             }";
+        assert_eq!(run(source).1.unwrap(), Value::Integer(42));
+    }
+
+    #[test]
+    fn evaluates_to_the_last_statement_without_a_return() {
+        assert_eq!(run("defun main ():integer { 1\n 2\n 3 }").1.unwrap(), Value::Integer(3));
+    }
+
+    #[test]
+    fn returns_from_inside_nested_loops() {
+        let (_, result) = run(
+            "defun main ():integer {
+                 i : integer = 0
+                 while true {
+                     while true {
+                         i := i + 1
+                         if i == 7 { return i * 2 }
+                     }
+                 }
+                 0
+             }",
+        );
+        assert_eq!(result.unwrap(), Value::Integer(14));
+    }
+
+    #[test]
+    fn a_return_unwinds_only_its_own_call() {
+        let (_, result) = run(
+            "defun first (n:integer):integer { while true { return n } 0 }
+             defun main ():integer { first(1) + first(2) + 3 }",
+        );
+        assert_eq!(result.unwrap(), Value::Integer(6));
+    }
+
+    #[test]
+    fn break_and_continue_target_the_innermost_loop() {
+        let (_, result) = run(
+            "defun main ():integer {
+                 total : integer = 0
+                 i : integer = 0
+                 while i < 5 {
+                     i := i + 1
+                     if i == 2 { continue }
+                     j : integer = 0
+                     while true {
+                         j := j + 1
+                         if j > i { break }
+                         total := total + 1
+                     }
+                 }
+                 total
+             }",
+        );
+        assert_eq!(result.unwrap(), Value::Integer(1 + 3 + 4 + 5));
+    }
+
+    #[test]
+    fn recurses() {
+        let (_, result) = run(
+            "defun fib (n:integer):integer { if n < 2 { return n } fib(n - 1) + fib(n - 2) }
+             defun main ():integer { fib(10) }",
+        );
+        assert_eq!(result.unwrap(), Value::Integer(55));
+    }
+
+    #[test]
+    fn traps_on_overflow() {
+        let err = trap("defun main ():integer { x : u8 = 200\n y : u8 = x + 100\n 0 }");
+        assert_eq!(err.code, ErrorCode::ArithmeticOverflow);
+        let err = trap("defun main ():integer { x : i32 = 0 - 2147483647\n x := x - 2\n 0 }");
+        assert_eq!(err.code, ErrorCode::ArithmeticOverflow);
+        let err = trap("defun main ():integer { x : i8 = -127\n x := x - 1\n y : i8 = -x\n 0 }");
+        assert_eq!(err.code, ErrorCode::ArithmeticOverflow);
+    }
+
+    #[test]
+    fn traps_on_a_shift_as_wide_as_the_type() {
+        let err = trap("defun main ():integer { x : u8 = 1\n n : u8 = 8\n y : u8 = x << n\n 0 }");
+        assert_eq!(err.code, ErrorCode::ArithmeticOverflow);
+    }
+
+    #[test]
+    fn traps_on_division_by_zero() {
+        let err = trap("defun main ():integer { x : integer = 0\n 1 / x }");
+        assert_eq!(err.code, ErrorCode::DivisionByZero);
+        let err = trap("defun main ():integer { x : integer = 0\n 1 % x }");
+        assert_eq!(err.code, ErrorCode::DivisionByZero);
+    }
+
+    #[test]
+    fn a_trap_reports_where_it_happened() {
+        let source = "defun div (a:integer, b:integer):integer { a / b }\ndefun main ():integer { div(1, 0) }";
+        let err = trap(source);
+        assert_eq!(&source[err.span.start..err.span.end], "a / b");
+    }
+}
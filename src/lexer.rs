@@ -1,47 +1,489 @@
+use crate::error::Diagnostic;
+use crate::error_codes::ErrorCode;
+use crate::span::{FileId, Span};
 
-pub const WHITESPACE: &str = " \r\n";
-pub const DELIMITERS: &str = " \r\n,():";
+pub const WHITESPACE: &str = " \t\r\n";
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Identifier,
+    Integer,
+    Float,
+    String,
+    Char,
+    Defun,
+    Struct,
+    Return,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    True,
+    False,
+    As,
+    Colon,
+    ColonEquals,
+    Equals,
+    EqualsEquals,
+    BangEquals,
+    Less,
+    LessEquals,
+    LessLess,
+    Greater,
+    GreaterEquals,
+    GreaterGreater,
+    Plus,
+    Minus,
+    Arrow,
+    Star,
+    Slash,
+    Percent,
+    Bang,
+    Ampersand,
+    AmpersandAmpersand,
+    Pipe,
+    PipePipe,
+    Caret,
+    Comma,
+    Semicolon,
+    Dot,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    DocComment,
+    EndOfFile,
+}
+
+/// Every operator and punctuation token, longest first so that the first
+/// match is also the longest one.
+const OPERATORS: &[(&str, TokenKind)] = &[
+    (":=", TokenKind::ColonEquals),
+    ("==", TokenKind::EqualsEquals),
+    ("!=", TokenKind::BangEquals),
+    ("<=", TokenKind::LessEquals),
+    ("<<", TokenKind::LessLess),
+    (">=", TokenKind::GreaterEquals),
+    (">>", TokenKind::GreaterGreater),
+    ("->", TokenKind::Arrow),
+    ("&&", TokenKind::AmpersandAmpersand),
+    ("||", TokenKind::PipePipe),
+    (":", TokenKind::Colon),
+    ("=", TokenKind::Equals),
+    ("<", TokenKind::Less),
+    (">", TokenKind::Greater),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Star),
+    ("/", TokenKind::Slash),
+    ("%", TokenKind::Percent),
+    ("!", TokenKind::Bang),
+    ("&", TokenKind::Ampersand),
+    ("|", TokenKind::Pipe),
+    ("^", TokenKind::Caret),
+    (",", TokenKind::Comma),
+    (";", TokenKind::Semicolon),
+    (".", TokenKind::Dot),
+    ("(", TokenKind::LeftParen),
+    (")", TokenKind::RightParen),
+    ("{", TokenKind::LeftBrace),
+    ("}", TokenKind::RightBrace),
+    ("[", TokenKind::LeftBracket),
+    ("]", TokenKind::RightBracket),
+];
+
+impl TokenKind {
+    /// The fixed spelling of an operator or punctuation token.
+    pub fn text(&self) -> Option<&'static str> {
+        OPERATORS.iter().find(|(_, kind)| kind == self).map(|(text, _)| *text)
+    }
+
+    fn from_word(word: &str) -> Self {
+        match word {
+            "defun" => TokenKind::Defun,
+            "struct" => TokenKind::Struct,
+            "return" => TokenKind::Return,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "while" => TokenKind::While,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "as" => TokenKind::As,
+            _ => TokenKind::Identifier,
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn digits_length(text: &str) -> usize {
+    text.find(|c: char| !c.is_ascii_digit() && c != '_').unwrap_or(text.len())
+}
+
+/// The length and kind of the number at the start of `text`, which starts
+/// with a digit or with `.` and a digit. A number is a float if it has a
+/// fraction, an exponent or a float suffix, as in `3.14`, `.5`, `1e-9` and
+/// `2f32`; any letters after it are left for the parser to reject as a
+/// suffix.
+fn number_length(text: &str) -> (usize, TokenKind) {
+    let bytes = text.as_bytes();
+    let digit_at = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_digit);
+    let word_end = |i: usize| i + text[i..].find(|c| !is_word_char(c)).unwrap_or(text.len() - i);
+
+    let prefix = text.get(..2).map(str::to_ascii_lowercase);
+    if matches!(prefix.as_deref(), Some("0x" | "0o" | "0b")) {
+        return (word_end(0), TokenKind::Integer);
+    }
+
+    let mut fractional = false;
+    let mut i = digits_length(text);
+    if bytes.get(i) == Some(&b'.') && digit_at(i + 1) {
+        i += 1 + digits_length(&text[i + 1..]);
+        fractional = true;
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        let sign = matches!(bytes.get(i + 1), Some(b'+' | b'-')) as usize;
+        if digit_at(i + 1 + sign) {
+            i += 1 + sign + digits_length(&text[i + 1 + sign..]);
+            fractional = true;
+        }
+    }
+    let end = word_end(i);
+    let suffix = &text[i..end];
+    if fractional || suffix == "f32" || suffix == "f64" {
+        (end, TokenKind::Float)
+    } else {
+        (end, TokenKind::Integer)
+    }
+}
+
+/// The length of the string or character literal at the start of `text`,
+/// quotes included, or `None` if it is never closed. A backslash escapes
+/// the character after it, so `"\""` is a single literal. A string may span
+/// lines, but a character literal ends with its line.
+fn quoted_length(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' if quote == '\'' => return None,
+            _ if c == quote => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `line` and `column` are 1-based and track `beginning`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
+    pub kind: TokenKind,
+    pub file: FileId,
     pub beginning: usize,
     pub end: usize,
+    pub line: usize,
+    pub column: usize,
 }
 
 impl Token {
-    pub fn new(beginning: usize, end: usize) -> Self {
-        Token { beginning, end }
+    /// An empty token at the very start of `file`, ready to be advanced.
+    pub fn new(file: FileId) -> Self {
+        Token {
+            kind: TokenKind::EndOfFile,
+            file,
+            beginning: 0,
+            end: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.beginning..self.end]
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.file, self.beginning, self.end, self.line, self.column)
+    }
+
+    /// An empty span just past the end of this token.
+    pub fn end_span(&self, source: &str) -> Span {
+        let mut end = self.clone();
+        end.advance_to(source, self.end);
+        Span::new(self.file, self.end, self.end, end.line, end.column)
+    }
+
+    /// Move `beginning` forward to `offset`, keeping line and column in step.
+    fn advance_to(&mut self, source: &str, offset: usize) {
+        for c in source[self.beginning..offset].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.beginning = offset;
     }
 }
 
+#[allow(dead_code)] // for debugging the lexer
 pub fn print_token(token: &Token, source: &str) {
-    println!("{}", &source[token.beginning..token.end]);
+    println!("{:?}: {}", token.kind, token.text(source));
 }
 
-pub fn lex(source: &str, token: &mut Token) -> Result<(), String> {
-    if source.is_empty() || token.beginning >= source.len() {
-        return Err("Cannot lex empty source.".to_string());
+/// `///` starts a doc comment, but `////` and beyond are plain comments.
+fn is_doc_comment(text: &str) -> bool {
+    text.starts_with("///") && !text.starts_with("////")
+}
+
+/// Skip whitespace, `//` line comments and (nestable) `/* */` block
+/// comments, stopping at the next real token or doc comment.
+fn skip_trivia(source: &str, token: &mut Token) -> Result<(), Diagnostic> {
+    loop {
+        let rest = &source[token.beginning..];
+        let whitespace: usize = rest
+            .chars()
+            .take_while(|c| WHITESPACE.contains(*c))
+            .map(char::len_utf8)
+            .sum();
+        let rest = &rest[whitespace..];
+
+        let skipped = if is_doc_comment(rest) {
+            0
+        } else if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            let mut depth = 0;
+            let mut i = 0;
+            loop {
+                if rest[i..].starts_with("/*") {
+                    depth += 1;
+                    i += 2;
+                } else if rest[i..].starts_with("*/") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break i;
+                    }
+                } else if let Some(c) = rest[i..].chars().next() {
+                    i += c.len_utf8();
+                } else {
+                    token.advance_to(source, token.beginning + whitespace);
+                    let span = Span { end: source.len(), ..token.span() };
+                    return Err(Diagnostic::error(ErrorCode::UnterminatedBlockComment, span, "Unterminated block comment"));
+                }
+            }
+        } else {
+            0
+        };
+
+        token.advance_to(source, token.beginning + whitespace + skipped);
+        if whitespace + skipped == 0 {
+            return Ok(());
+        }
     }
+}
 
-    token.beginning += source[token.beginning..]
-        .chars()
-        .take_while(|c| WHITESPACE.contains(*c))
-        .count();
+/// Lex the token starting at `token.beginning`, skipping any leading
+/// whitespace and comments. Reaching the end of the source yields an empty
+/// `EndOfFile` token rather than an error.
+pub fn lex(source: &str, token: &mut Token) -> Result<(), Diagnostic> {
+    if token.beginning > source.len() {
+        return Err(Diagnostic::error(
+            ErrorCode::LexPastEnd,
+            token.span(),
+            "Cannot lex past the end of the source.",
+        ));
+    }
+
+    skip_trivia(source, token)?;
     token.end = token.beginning;
     if token.end >= source.len() {
+        token.kind = TokenKind::EndOfFile;
+        return Ok(());
+    }
+
+    let rest = &source[token.beginning..];
+    if is_doc_comment(rest) {
+        token.end += rest.find('\n').unwrap_or(rest.len());
+        token.kind = TokenKind::DocComment;
+        return Ok(());
+    }
+
+    let first = rest.chars().next().unwrap();
+    if first.is_ascii_digit() || (first == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit())) {
+        let (length, kind) = number_length(rest);
+        token.end += length;
+        token.kind = kind;
+        return Ok(());
+    }
+    if first == '"' || first == '\'' {
+        let (kind, what) = if first == '"' { (TokenKind::String, "string") } else { (TokenKind::Char, "character") };
+        let Some(length) = quoted_length(rest) else {
+            return Err(Diagnostic::error(
+                ErrorCode::UnterminatedString,
+                Span { end: token.beginning + 1, ..token.span() },
+                format!("Unterminated {} literal", what),
+            )
+            .with_help(format!("add a closing {} to end the literal", first)));
+        };
+        token.end += length;
+        token.kind = kind;
+        return Ok(());
+    }
+    if is_word_char(first) {
+        let length = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+        token.end += length;
+        token.kind = TokenKind::from_word(token.text(source));
         return Ok(());
     }
 
-    token.end += source[token.beginning..]
-        .chars()
-        .take_while(|c| !DELIMITERS.contains(*c))
-        .count();
-    if token.end == token.beginning {
-        token.end += 1;
+    match OPERATORS.iter().find(|(text, _)| rest.starts_with(text)) {
+        Some((text, kind)) => {
+            token.end += text.len();
+            token.kind = *kind;
+            Ok(())
+        }
+        None => Err(Diagnostic::error(
+            ErrorCode::UnexpectedCharacter,
+            Span { end: token.beginning + first.len_utf8(), ..token.span() },
+            format!("Unexpected character: {}", first),
+        )),
     }
-    Ok(())
 }
 
-pub fn token_string_equalp(string: &str, token: &Token, source: &str) -> bool {
-    &source[token.beginning..token.end] == string
+/// Lex the token following `token` in place.
+pub fn next_token(source: &str, token: &mut Token) -> Result<(), Diagnostic> {
+    token.advance_to(source, token.end);
+    lex(source, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kinds of every token in `source`, up to but not including the
+    /// end of file.
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let mut token = Token::new(0);
+        let mut kinds = Vec::new();
+        loop {
+            next_token(source, &mut token).unwrap();
+            if token.kind == TokenKind::EndOfFile {
+                return kinds;
+            }
+            kinds.push(token.kind);
+        }
+    }
+
+    #[test]
+    fn lexes_every_operator_with_and_without_whitespace() {
+        for &(text, kind) in OPERATORS {
+            let expected = vec![TokenKind::Identifier, kind, TokenKind::Identifier];
+            assert_eq!(kinds(&format!("a{}b", text)), expected, "a{}b", text);
+            assert_eq!(kinds(&format!("a {} b", text)), expected, "a {} b", text);
+        }
+    }
+
+    #[test]
+    fn operator_text_round_trips() {
+        for &(text, kind) in OPERATORS {
+            assert_eq!(kind.text(), Some(text));
+        }
+    }
+
+    #[test]
+    fn takes_the_longest_operator() {
+        use TokenKind::*;
+        assert_eq!(kinds("a:=b"), [Identifier, ColonEquals, Identifier]);
+        assert_eq!(kinds("a: =b"), [Identifier, Colon, Equals, Identifier]);
+        assert_eq!(kinds("a:b"), [Identifier, Colon, Identifier]);
+        assert_eq!(kinds("a->b"), [Identifier, Arrow, Identifier]);
+        assert_eq!(kinds("a- >b"), [Identifier, Minus, Greater, Identifier]);
+        assert_eq!(kinds("a&&b"), [Identifier, AmpersandAmpersand, Identifier]);
+        assert_eq!(kinds("a& &b"), [Identifier, Ampersand, Ampersand, Identifier]);
+        assert_eq!(kinds("a&&&b"), [Identifier, AmpersandAmpersand, Ampersand, Identifier]);
+        assert_eq!(kinds("x<<=y"), [Identifier, LessLess, Equals, Identifier]);
+        assert_eq!(kinds("x<=y"), [Identifier, LessEquals, Identifier]);
+        assert_eq!(kinds("x>>y"), [Identifier, GreaterGreater, Identifier]);
+        assert_eq!(kinds("x||y|z"), [Identifier, PipePipe, Identifier, Pipe, Identifier]);
+    }
+
+    #[test]
+    fn rejects_an_unknown_character() {
+        let mut token = Token::new(0);
+        let err = next_token("a $ b", &mut token).and_then(|_| next_token("a $ b", &mut token)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnexpectedCharacter);
+        assert_eq!((err.span.start, err.span.end), (2, 3));
+    }
+
+    #[test]
+    fn a_quoted_literal_ends_at_its_unescaped_quote() {
+        assert_eq!(quoted_length(r#""abc" rest"#), Some(5));
+        assert_eq!(quoted_length(r#""a\"b""#), Some(6));
+        assert_eq!(quoted_length(r#""a\\" b""#), Some(5));
+        assert_eq!(quoted_length(r"'\''"), Some(4));
+        assert_eq!(quoted_length("\"two\nlines\""), Some(11));
+        assert_eq!(quoted_length("'a\nb'"), None);
+        assert_eq!(quoted_length(r#""abc\"#), None);
+        assert_eq!(quoted_length(r#""abc"#), None);
+    }
+
+    #[test]
+    fn an_unterminated_literal_points_at_its_opening_quote() {
+        let source = "a := 1\nb := \"abc\nc := 2";
+        let mut token = Token::new(0);
+        let err = loop {
+            if let Err(err) = next_token(source, &mut token) {
+                break err;
+            }
+        };
+        assert_eq!(err.code, ErrorCode::UnterminatedString);
+        assert_eq!(err.message, "Unterminated string literal");
+        assert_eq!((err.span.start, err.span.end, err.span.line, err.span.column), (12, 13, 2, 6));
+
+        let err = next_token("'a", &mut Token::new(0)).unwrap_err();
+        assert_eq!(err.message, "Unterminated character literal");
+        assert_eq!((err.span.start, err.span.end), (0, 1));
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        use TokenKind::*;
+        assert_eq!(kinds("a /* x /* y */ z */ b"), [Identifier, Identifier]);
+        assert_eq!(kinds("a /**/ b/*/**/*/c"), [Identifier, Identifier, Identifier]);
+        assert_eq!(kinds("a /* // */ b"), [Identifier, Identifier]);
+    }
+
+    #[test]
+    fn an_unterminated_block_comment_runs_from_its_start_to_the_end() {
+        let source = "a\n  /* x /* y */ z";
+        let mut token = Token::new(0);
+        next_token(source, &mut token).unwrap();
+        let err = next_token(source, &mut token).unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnterminatedBlockComment);
+        assert_eq!((err.span.start, err.span.end), (4, source.len()));
+        assert_eq!((err.span.line, err.span.column), (2, 3));
+    }
+
+    #[test]
+    fn three_slashes_start_a_doc_comment_but_four_do_not() {
+        use TokenKind::*;
+        assert_eq!(kinds("/// doc\na"), [DocComment, Identifier]);
+        assert_eq!(kinds("///\na"), [DocComment, Identifier]);
+        assert_eq!(kinds("//// note\na"), [Identifier]);
+        assert_eq!(kinds("// note\na"), [Identifier]);
+        assert_eq!(kinds("/* /// */ a"), [Identifier]);
+    }
 }
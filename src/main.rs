@@ -1,34 +1,189 @@
+#![allow(clippy::result_large_err)] // diagnostics are only built on the error path
+
+mod compile;
 mod environment;
 mod error;
+mod error_codes;
 mod file_io;
+mod fold;
+mod interp;
 mod parser;
+mod resolver;
 mod lexer;
 mod node;
+mod span;
+mod typechecker;
+
+use std::env;
+use std::process::ExitCode;
+
+use compile::compile;
+use error::{ColorChoice, Diagnostic, ErrorFormat};
+use error_codes::ErrorCode;
+use interp::{Interpreter, Value};
+use node::{Node, NodeValue};
+use span::{FileId, SourceMap, Span};
+
+fn usage(program: &str) {
+    eprintln!("USAGE: {} [--run] [--color=auto|always|never] [--error-format=human|json] <path>...", program);
+    eprintln!("  Compile each source file at <path>; `-` reads from standard input.");
+    eprintln!("  With --run, interpret each program and exit with the value returned by `main`.");
+    eprintln!("USAGE: {} --explain <code>", program);
+    eprintln!("  Print a detailed explanation of the error with the given code, e.g. CL0001.");
+}
+
+fn explain(program: &str, code: &str) -> ExitCode {
+    match ErrorCode::from_code(code) {
+        Some(error_code) => {
+            println!("{}", error_code.explanation());
+            ExitCode::SUCCESS
+        }
+        None => {
+            eprintln!("ERROR: {} is not a valid error code", code);
+            usage(program);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Compile the source file `file`, then run it or print its syntax tree,
+/// yielding its diagnostics. Running sets `exit_value` from the result of
+/// `main`.
+fn process_file(source_map: &SourceMap, file: FileId, run: bool, exit_value: &mut u8) -> Vec<Diagnostic> {
+    let (program, mut diagnostics) = compile(&source_map.file(file).source, file);
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+    if !run {
+        program.print(0);
+        return diagnostics;
+    }
+    match Interpreter::new().run_program(&program).and_then(|value| exit_status(&program, &value)) {
+        Ok(status) => *exit_value = status,
+        Err(err) => diagnostics.push(err),
+    }
+    diagnostics
+}
 
-use parser::{parse_expr, ParsingContext};
-
-fn main() {
-    let tests = vec![
-        "a : integer = 69",
-        "a := 420",
-        "b : integer",
-        "b := 42",
-        "defun foo (a:integer, b:integer):integer { a := a + b }",
-    ];
-
-    for (i, test) in tests.iter().enumerate() {
-        println!("Test {}: {}", i + 1, test);
-        let mut context = ParsingContext::new();
-        let mut end = 0;
-        match parse_expr(&mut context, test, &mut end) {
-            Ok(result) => {
-                println!("Parsed result:");
-                result.print(0);
+/// The exit status for a `program` whose `main` returned `value`. An exit
+/// status is a byte; anything else is reported rather than truncated,
+/// perhaps to a misleading 0.
+fn exit_status(program: &Node, value: &Value) -> Result<u8, Diagnostic> {
+    u8::try_from(value.as_integer()).map_err(|_| {
+        let main = program.iter_children().find(|form| {
+            matches!(&form.value, Some(NodeValue::FunctionDefinition { name, .. }) if name == "main")
+        });
+        Diagnostic::error(
+            ErrorCode::InvalidExitStatus,
+            main.map_or(program.span, |main| main.span),
+            format!("main returned {}, which is not an exit status from 0 to 255", value.as_integer()),
+        )
+    })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let program = args.first().map_or("compiler", String::as_str);
+    let mut color = ColorChoice::Auto;
+    let mut error_format = ErrorFormat::Human;
+    let mut run = false;
+    let mut paths = Vec::new();
+    let mut args_iter = args[1..].iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "--explain" {
+            return match args_iter.next() {
+                Some(code) => explain(program, code),
+                None => {
+                    usage(program);
+                    ExitCode::from(2)
+                }
+            };
+        } else if arg == "--run" {
+            run = true;
+        } else if let Some(code) = arg.strip_prefix("--explain=") {
+            return explain(program, code);
+        } else if let Some(value) = arg.strip_prefix("--color=") {
+            match ColorChoice::from_flag(value) {
+                Some(choice) => color = choice,
+                None => {
+                    eprintln!("ERROR: Invalid --color value: {}", value);
+                    usage(program);
+                    return ExitCode::from(2);
+                }
+            }
+        } else if let Some(value) = arg.strip_prefix("--error-format=") {
+            match ErrorFormat::from_flag(value) {
+                Some(format) => error_format = format,
+                None => {
+                    eprintln!("ERROR: Invalid --error-format value: {}", value);
+                    usage(program);
+                    return ExitCode::from(2);
+                }
+            }
+        } else if arg.starts_with("--") {
+            eprintln!("ERROR: Unknown option: {}", arg);
+            usage(program);
+            return ExitCode::from(2);
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        usage(program);
+        return ExitCode::from(2);
+    }
+
+    let mut source_map = SourceMap::new();
+    let mut failed = false;
+    let mut first_code = None;
+    let mut exit_value = 0;
+    for path in paths {
+        let name = if path == "-" { "<stdin>" } else { path };
+        let diagnostics = match file_io::source_contents(path) {
+            Ok(contents) => {
+                let file = source_map.add_file(name, contents);
+                process_file(&source_map, file, run, &mut exit_value)
             }
             Err(err) => {
-                println!("Error: {}", err);
+                // Recorded as an empty file, for the diagnostic to name.
+                let file = source_map.add_file(name, String::new());
+                vec![Diagnostic::error(
+                    ErrorCode::UnreadableFile,
+                    Span::new(file, 0, 0, 1, 1),
+                    format!("Failed to read {}: {}", path, err),
+                )]
             }
+        };
+        for err in &diagnostics {
+            error::print_error(&source_map, err, error_format, color);
+        }
+        first_code = first_code.or(diagnostics.first().map(|err| err.code));
+        failed |= !diagnostics.is_empty();
+    }
+
+    if let (Some(code), ErrorFormat::Human) = (first_code, error_format) {
+        eprintln!("For more information about an error, try `{} --explain {}`.", program, code.code());
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::from(exit_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_exit_status_is_a_byte() {
+        let source = "x : integer = 1\ndefun main () : integer { 0 }";
+        let (program, _) = compile(source, 0);
+        assert_eq!(exit_status(&program, &Value::Integer(255)).unwrap(), 255);
+        for value in [256, -1] {
+            let err = exit_status(&program, &Value::Integer(value)).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidExitStatus);
+            assert_eq!(&source[err.span.start..err.span.end], "defun main () : integer { 0 }");
         }
-        println!();
     }
 }
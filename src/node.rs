@@ -1,29 +1,172 @@
 use std::fmt;
 
+use crate::span::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum NodeType {
     None,
     Integer,
+    Float,
+    Boolean,
+    String,
+    Char,
     Symbol,
     VariableDeclaration,
     VariableDeclarationInitialized,
     VariableAssignment,
     FunctionDefinition,
+    FunctionCall,
+    StructDefinition,
+    StructLiteral,
+    FieldAccess,
+    FieldAssignment,
+    Return,
+    If,
+    While,
+    Break,
+    Continue,
+    BinaryOp,
+    UnaryOp,
+    Cast,
     Program,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    LogicalAnd,
+    LogicalOr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOperator {
+    /// Whether the operator compares its operands, yielding a boolean.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        )
+    }
+
+    /// Whether the operator is `&&` or `||`, which take booleans.
+    pub fn is_logical(&self) -> bool {
+        matches!(self, BinaryOperator::LogicalAnd | BinaryOperator::LogicalOr)
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::LogicalAnd => "&&",
+            BinaryOperator::LogicalOr => "||",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+impl UnaryOperator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: String,
+    pub span: Span,
+}
+
+/// A field in a struct definition.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub field_type: String,
+    pub span: Span,
+}
+
+/// A field's value in a struct literal.
+#[derive(Debug, Clone)]
+pub struct FieldInit {
+    pub name: String,
+    pub value: Node,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum NodeValue {
-    Integer(i64),
+    Integer(i128),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Char(char),
     Symbol(String),
     VariableDeclaration { name: String, var_type: String },
     VariableAssignment { name: String, value: Box<Node> },
     FunctionDefinition {
         name: String,
-        params: Vec<(String, String)>,
+        params: Vec<Parameter>,
         return_type: String,
         body: Vec<Node>,
     },
+    FunctionCall { name: String, args: Vec<Node> },
+    StructDefinition { name: String, fields: Vec<Field> },
+    /// The type checker puts `fields` in the order of the definition.
+    StructLiteral { name: String, fields: Vec<FieldInit> },
+    FieldAccess { value: Box<Node>, field: String },
+    /// `target` is a `FieldAccess` on a variable, or on another such
+    /// `FieldAccess`.
+    FieldAssignment { target: Box<Node>, value: Box<Node> },
+    Return { value: Option<Box<Node>> },
+    /// `else if` is an `else_body` holding a single `If`.
+    If { condition: Box<Node>, then_body: Vec<Node>, else_body: Option<Vec<Node>> },
+    While { condition: Box<Node>, body: Vec<Node> },
+    BinaryOp { operator: BinaryOperator, lhs: Box<Node>, rhs: Box<Node> },
+    UnaryOp { operator: UnaryOperator, operand: Box<Node> },
+    /// `value as target`.
+    Cast { value: Box<Node>, target: String },
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +175,11 @@ pub struct Node {
     pub value: Option<NodeValue>,
     pub children: Vec<Node>,
     pub next_child: Option<Box<Node>>,
+    pub span: Span,
+    pub doc: Option<String>,
+    /// The type of an expression node, filled in by the type checker. A
+    /// literal with a type suffix already has its type from the parser.
+    pub type_name: Option<String>,
 }
 
 impl Node {
@@ -41,9 +189,28 @@ impl Node {
             value,
             children: Vec::new(),
             next_child: None,
+            span: Span::default(),
+            doc: None,
+            type_name: None,
         }
     }
 
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    /// Whether this node declares something a doc comment can describe.
+    pub fn is_declaration(&self) -> bool {
+        matches!(
+            self.node_type,
+            NodeType::VariableDeclaration
+                | NodeType::VariableDeclarationInitialized
+                | NodeType::FunctionDefinition
+                | NodeType::StructDefinition
+        )
+    }
+
     pub fn add_child(&mut self, new_child: Node) {
         if let Some(last_child) = self.children.last_mut() {
             let mut last_child = last_child;
@@ -56,6 +223,14 @@ impl Node {
         }
     }
 
+    /// Iterate over every child, following the `next_child` chain.
+    pub fn iter_children(&self) -> impl Iterator<Item = &Node> {
+        self.children
+            .iter()
+            .flat_map(|child| std::iter::successors(Some(child), |node| node.next_child.as_deref()))
+    }
+
+    #[allow(dead_code)]
     pub fn compare(a: &Node, b: &Node) -> bool {
         if a.node_type != b.node_type {
             return false;
@@ -69,6 +244,34 @@ impl Node {
                     false
                 }
             }
+            NodeType::Float => {
+                if let (Some(NodeValue::Float(a_val)), Some(NodeValue::Float(b_val))) = (&a.value, &b.value) {
+                    a_val == b_val
+                } else {
+                    false
+                }
+            }
+            NodeType::Boolean => {
+                if let (Some(NodeValue::Boolean(a_val)), Some(NodeValue::Boolean(b_val))) = (&a.value, &b.value) {
+                    a_val == b_val
+                } else {
+                    false
+                }
+            }
+            NodeType::String => {
+                if let (Some(NodeValue::String(a_val)), Some(NodeValue::String(b_val))) = (&a.value, &b.value) {
+                    a_val == b_val
+                } else {
+                    false
+                }
+            }
+            NodeType::Char => {
+                if let (Some(NodeValue::Char(a_val)), Some(NodeValue::Char(b_val))) = (&a.value, &b.value) {
+                    a_val == b_val
+                } else {
+                    false
+                }
+            }
             NodeType::Symbol => {
                 if let (Some(NodeValue::Symbol(ref a_val)), Some(NodeValue::Symbol(ref b_val))) = (&a.value, &b.value) {
                     a_val == b_val
@@ -80,19 +283,41 @@ impl Node {
         }
     }
 
-    pub fn from_integer(value: i64) -> Self {
+    pub fn from_integer(value: i128) -> Self {
         Node::new(NodeType::Integer, Some(NodeValue::Integer(value)))
     }
 
+    pub fn from_float(value: f64) -> Self {
+        Node::new(NodeType::Float, Some(NodeValue::Float(value)))
+    }
+
+    pub fn from_boolean(value: bool) -> Self {
+        Node::new(NodeType::Boolean, Some(NodeValue::Boolean(value)))
+    }
+
+    pub fn from_string(value: &str) -> Self {
+        Node::new(NodeType::String, Some(NodeValue::String(value.to_string())))
+    }
+
+    pub fn from_char(value: char) -> Self {
+        Node::new(NodeType::Char, Some(NodeValue::Char(value)))
+    }
+
     pub fn from_symbol(symbol: &str) -> Self {
         Node::new(NodeType::Symbol, Some(NodeValue::Symbol(symbol.to_string())))
     }
 
+    #[allow(dead_code)]
     pub fn from_symbol_buffer(buffer: &str) -> Self {
         Node::new(NodeType::Symbol, Some(NodeValue::Symbol(buffer.to_string())))
     }
 
     pub fn print(&self, indent_level: usize) {
+        if let Some(doc) = &self.doc {
+            for line in doc.lines() {
+                println!("{:indent$}DOC: {}", "", line, indent = indent_level);
+            }
+        }
         for _ in 0..indent_level {
             print!(" ");
         }
@@ -103,6 +328,26 @@ impl Node {
                     println!("INT:{}", value);
                 }
             }
+            NodeType::Float => {
+                if let Some(NodeValue::Float(value)) = &self.value {
+                    println!("FLOAT:{:?}", value);
+                }
+            }
+            NodeType::Boolean => {
+                if let Some(NodeValue::Boolean(value)) = &self.value {
+                    println!("BOOL:{}", value);
+                }
+            }
+            NodeType::String => {
+                if let Some(NodeValue::String(value)) = &self.value {
+                    println!("STRING:{:?}", value);
+                }
+            }
+            NodeType::Char => {
+                if let Some(NodeValue::Char(value)) = &self.value {
+                    println!("CHAR:{:?}", value);
+                }
+            }
             NodeType::Symbol => {
                 print!("SYM");
                 if let Some(NodeValue::Symbol(ref symbol)) = &self.value {
@@ -133,8 +378,8 @@ impl Node {
             NodeType::FunctionDefinition => {
                 if let Some(NodeValue::FunctionDefinition { name, params, return_type, body }) = &self.value {
                     println!("FUNCTION DEFINITION: {} (", name);
-                    for (param_name, param_type) in params {
-                        print!("{}: {}, ", param_name, param_type);
+                    for param in params {
+                        print!("{}: {}, ", param.name, param.param_type);
                     }
                     println!("): {}", return_type);
                     for stmt in body {
@@ -142,7 +387,98 @@ impl Node {
                     }
                 }
             }
-            NodeType::Program => println!("PROGRAM"),
+            NodeType::FunctionCall => {
+                if let Some(NodeValue::FunctionCall { name, args }) = &self.value {
+                    println!("FUNCTION CALL: {}", name);
+                    for arg in args {
+                        arg.print(indent_level + 4);
+                    }
+                }
+            }
+            NodeType::StructDefinition => {
+                if let Some(NodeValue::StructDefinition { name, fields }) = &self.value {
+                    println!("STRUCT DEFINITION: {}", name);
+                    for field in fields {
+                        println!("{:indent$}{}: {}", "", field.name, field.field_type, indent = indent_level + 4);
+                    }
+                }
+            }
+            NodeType::StructLiteral => {
+                if let Some(NodeValue::StructLiteral { name, fields }) = &self.value {
+                    println!("STRUCT LITERAL: {}", name);
+                    for field in fields {
+                        println!("{:indent$}FIELD: {}", "", field.name, indent = indent_level + 4);
+                        field.value.print(indent_level + 8);
+                    }
+                }
+            }
+            NodeType::FieldAccess => {
+                if let Some(NodeValue::FieldAccess { value, field }) = &self.value {
+                    println!("FIELD ACCESS: {}", field);
+                    value.print(indent_level + 4);
+                }
+            }
+            NodeType::FieldAssignment => {
+                if let Some(NodeValue::FieldAssignment { target, value }) = &self.value {
+                    println!("FIELD ASSIGNMENT: {} := {}", target, value);
+                }
+            }
+            NodeType::Return => {
+                println!("RETURN");
+                if let Some(NodeValue::Return { value: Some(value) }) = &self.value {
+                    value.print(indent_level + 4);
+                }
+            }
+            NodeType::If => {
+                if let Some(NodeValue::If { condition, then_body, else_body }) = &self.value {
+                    println!("IF: {}", condition);
+                    for stmt in then_body {
+                        stmt.print(indent_level + 4);
+                    }
+                    if let Some(else_body) = else_body {
+                        println!("{:indent$}ELSE", "", indent = indent_level);
+                        for stmt in else_body {
+                            stmt.print(indent_level + 4);
+                        }
+                    }
+                }
+            }
+            NodeType::While => {
+                if let Some(NodeValue::While { condition, body }) = &self.value {
+                    println!("WHILE: {}", condition);
+                    for stmt in body {
+                        stmt.print(indent_level + 4);
+                    }
+                }
+            }
+            NodeType::Break => println!("BREAK"),
+            NodeType::Continue => println!("CONTINUE"),
+            NodeType::BinaryOp => {
+                if let Some(NodeValue::BinaryOp { operator, lhs, rhs }) = &self.value {
+                    println!("BINARY OP: {}", operator.symbol());
+                    lhs.print(indent_level + 4);
+                    rhs.print(indent_level + 4);
+                }
+            }
+            NodeType::UnaryOp => {
+                if let Some(NodeValue::UnaryOp { operator, operand }) = &self.value {
+                    println!("UNARY OP: {}", operator.symbol());
+                    operand.print(indent_level + 4);
+                }
+            }
+            NodeType::Cast => {
+                if let Some(NodeValue::Cast { value, target }) = &self.value {
+                    println!("CAST: {}", target);
+                    value.print(indent_level + 4);
+                }
+            }
+            NodeType::Error => println!("ERROR"),
+            NodeType::Program => {
+                println!("PROGRAM");
+                for child in self.iter_children() {
+                    child.print(indent_level + 4);
+                }
+            }
         }
     }
 }
@@ -158,6 +494,34 @@ impl fmt::Display for Node {
                     write!(f, "INT: <no value>")
                 }
             }
+            NodeType::Float => {
+                if let Some(NodeValue::Float(value)) = &self.value {
+                    write!(f, "FLOAT:{:?}", value)
+                } else {
+                    write!(f, "FLOAT: <no value>")
+                }
+            }
+            NodeType::Boolean => {
+                if let Some(NodeValue::Boolean(value)) = &self.value {
+                    write!(f, "BOOL:{}", value)
+                } else {
+                    write!(f, "BOOL: <no value>")
+                }
+            }
+            NodeType::String => {
+                if let Some(NodeValue::String(value)) = &self.value {
+                    write!(f, "STRING:{:?}", value)
+                } else {
+                    write!(f, "STRING: <no value>")
+                }
+            }
+            NodeType::Char => {
+                if let Some(NodeValue::Char(value)) = &self.value {
+                    write!(f, "CHAR:{:?}", value)
+                } else {
+                    write!(f, "CHAR: <no value>")
+                }
+            }
             NodeType::Symbol => {
                 if let Some(NodeValue::Symbol(symbol)) = &self.value {
                     write!(f, "SYM:{}", symbol)
@@ -189,8 +553,8 @@ impl fmt::Display for Node {
             NodeType::FunctionDefinition => {
                 if let Some(NodeValue::FunctionDefinition { name, params, return_type, body }) = &self.value {
                     write!(f, "FUNCTION DEFINITION: {} (", name)?;
-                    for (param_name, param_type) in params {
-                        write!(f, "{}: {}, ", param_name, param_type)?;
+                    for param in params {
+                        write!(f, "{}: {}, ", param.name, param.param_type)?;
                     }
                     write!(f, "): {}", return_type)?;
                     for stmt in body {
@@ -201,7 +565,119 @@ impl fmt::Display for Node {
                     write!(f, "FUNCTION DEFINITION: <no value>")
                 }
             }
+            NodeType::FunctionCall => {
+                if let Some(NodeValue::FunctionCall { name, args }) = &self.value {
+                    write!(f, "{}(", name)?;
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", arg)?;
+                    }
+                    write!(f, ")")
+                } else {
+                    write!(f, "FUNCTION CALL: <no value>")
+                }
+            }
+            NodeType::StructDefinition => {
+                if let Some(NodeValue::StructDefinition { name, fields }) = &self.value {
+                    write!(f, "STRUCT DEFINITION: {} {{ ", name)?;
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}: {}", field.name, field.field_type)?;
+                    }
+                    write!(f, " }}")
+                } else {
+                    write!(f, "STRUCT DEFINITION: <no value>")
+                }
+            }
+            NodeType::StructLiteral => {
+                if let Some(NodeValue::StructLiteral { name, fields }) = &self.value {
+                    write!(f, "{} {{ ", name)?;
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}: {}", field.name, field.value)?;
+                    }
+                    write!(f, " }}")
+                } else {
+                    write!(f, "STRUCT LITERAL: <no value>")
+                }
+            }
+            NodeType::FieldAccess => {
+                if let Some(NodeValue::FieldAccess { value, field }) = &self.value {
+                    write!(f, "{}.{}", value, field)
+                } else {
+                    write!(f, "FIELD ACCESS: <no value>")
+                }
+            }
+            NodeType::FieldAssignment => {
+                if let Some(NodeValue::FieldAssignment { target, value }) = &self.value {
+                    write!(f, "FIELD ASSIGNMENT: {} := {}", target, value)
+                } else {
+                    write!(f, "FIELD ASSIGNMENT: <no value>")
+                }
+            }
+            NodeType::Return => match &self.value {
+                Some(NodeValue::Return { value: Some(value) }) => write!(f, "RETURN {}", value),
+                _ => write!(f, "RETURN"),
+            },
+            NodeType::If => {
+                if let Some(NodeValue::If { condition, then_body, else_body }) = &self.value {
+                    write!(f, "IF {}", condition)?;
+                    for stmt in then_body {
+                        write!(f, "\n{}", stmt)?;
+                    }
+                    if let Some(else_body) = else_body {
+                        write!(f, "\nELSE")?;
+                        for stmt in else_body {
+                            write!(f, "\n{}", stmt)?;
+                        }
+                    }
+                    Ok(())
+                } else {
+                    write!(f, "IF: <no value>")
+                }
+            }
+            NodeType::While => {
+                if let Some(NodeValue::While { condition, body }) = &self.value {
+                    write!(f, "WHILE {}", condition)?;
+                    for stmt in body {
+                        write!(f, "\n{}", stmt)?;
+                    }
+                    Ok(())
+                } else {
+                    write!(f, "WHILE: <no value>")
+                }
+            }
+            NodeType::Break => write!(f, "BREAK"),
+            NodeType::Continue => write!(f, "CONTINUE"),
+            NodeType::BinaryOp => {
+                if let Some(NodeValue::BinaryOp { operator, lhs, rhs }) = &self.value {
+                    write!(f, "({} {} {})", lhs, operator.symbol(), rhs)
+                } else {
+                    write!(f, "BINARY OP: <no value>")
+                }
+            }
+            NodeType::UnaryOp => {
+                if let Some(NodeValue::UnaryOp { operator, operand }) = &self.value {
+                    write!(f, "({}{})", operator.symbol(), operand)
+                } else {
+                    write!(f, "UNARY OP: <no value>")
+                }
+            }
+            NodeType::Cast => {
+                if let Some(NodeValue::Cast { value, target }) = &self.value {
+                    write!(f, "({} as {})", value, target)
+                } else {
+                    write!(f, "CAST: <no value>")
+                }
+            }
             NodeType::Program => write!(f, "PROGRAM"),
+            NodeType::Error => write!(f, "ERROR"),
         }
     }
 }
@@ -1,216 +1,1281 @@
 use crate::environment::Environment;
-use crate::lexer::{lex, token_string_equalp, Token};
-use crate::node::{Node, NodeType, NodeValue};
+use crate::error::Diagnostic;
+use crate::error_codes::ErrorCode;
+use crate::lexer::{next_token, Token, TokenKind};
+use crate::node::{BinaryOperator, Field, FieldInit, Node, NodeType, NodeValue, Parameter, UnaryOperator};
+use crate::span::{FileId, Span};
+use crate::typechecker::{
+    float_type, integer_type, BOOLEAN, CHAR, DEFAULT_INTEGER, FLOAT_TYPES, INTEGER, INTEGER_TYPES, STRING, UNKNOWN, VOID,
+};
 
 #[derive(Debug)]
 pub struct ParsingContext {
     pub types: Environment,
-    pub variables: Environment,
+    /// How many blocks enclose the form being parsed; 0 at the top level.
+    pub block_depth: usize,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl ParsingContext {
     pub fn new() -> Self {
+        // Each type name maps to the type it stands for, and each struct
+        // to its definition.
         let mut types = Environment::new(None);
-        types.set(&Node::from_symbol("integer"), Node::from_integer(0)).unwrap();
+        let integers = INTEGER_TYPES.iter().map(|(name, ..)| *name);
+        let floats = FLOAT_TYPES.iter().map(|(name, _)| *name);
+        for name in integers.chain(floats).chain([BOOLEAN, CHAR, STRING, VOID]) {
+            types.set(&Node::from_symbol(name), Node::from_symbol(name)).unwrap();
+        }
+        types.set(&Node::from_symbol(INTEGER), Node::from_symbol(DEFAULT_INTEGER)).unwrap();
         ParsingContext {
             types,
-            variables: Environment::new(None),
+            block_depth: 0,
+            diagnostics: Vec::new(),
         }
     }
 }
 
-pub fn parse_integer(token: &Token, source: &str) -> Result<Node, ()> {
-    let token_str = &source[token.beginning..token.end];
-    if token_str == "0" {
-        Ok(Node::from_integer(0))
-    } else if let Ok(value) = token_str.parse::<i64>() {
-        Ok(Node::from_integer(value))
-    } else {
-        Err(())
-    }
+/// The offset of the first `_` in `digits` that does not sit between two
+/// digits of `radix`, as every `_` in a number must.
+fn misplaced_underscore(digits: &str, radix: u32) -> Option<usize> {
+    let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_digit(radix));
+    digits
+        .char_indices()
+        .find(|&(i, c)| c == '_' && !(is_digit(digits[..i].chars().next_back()) && is_digit(digits[i + 1..].chars().next())))
+        .map(|(i, _)| i)
+}
+
+/// A diagnostic for a `_` at `offset` within the literal `token`.
+fn underscore_error(token: &Token, source: &str, code: ErrorCode, offset: usize, what: &str) -> Diagnostic {
+    Diagnostic::error(
+        code,
+        span_within(token, source, token.beginning + offset, token.beginning + offset + 1),
+        format!("Misplaced `_` in {} literal: {}", what, token.text(source)),
+    )
+    .with_label(token.span(), format!("{} literal", what))
+    .with_help("`_` may only separate two digits, as in 1_000_000")
 }
 
-pub fn parse_expr(context: &mut ParsingContext, source: &str, end: &mut usize) -> Result<Node, String> {
-    let mut current_token = Token::new(*end, *end);
-    let mut result = Node::new(NodeType::None, None);
+/// Parse an integer literal, as in `42`, `0xff`, `0o17`, `0b1010` or
+/// `1_000_000`, with an optional type suffix, as in `42u8`. A suffix gives
+/// the literal its type; the type checker range checks it. A leading `-`
+/// is a unary operator rather than part of the literal.
+pub fn parse_integer(token: &Token, source: &str) -> Result<Node, Diagnostic> {
+    let text = token.text(source);
+    let invalid = |message: String| Diagnostic::error(ErrorCode::InvalidIntegerLiteral, token.span(), message);
+    let (radix, base_name, prefix_len) = match text.get(..2) {
+        Some("0x" | "0X") => (16, "hexadecimal", 2),
+        Some("0o" | "0O") => (8, "octal", 2),
+        Some("0b" | "0B") => (2, "binary", 2),
+        _ => (10, "decimal", 0),
+    };
+    let digits_end = text[prefix_len..]
+        .find(|c: char| !c.is_digit(radix) && c != '_')
+        .map_or(text.len(), |end| prefix_len + end);
+    let (digits, suffix) = (&text[prefix_len..digits_end], &text[digits_end..]);
+    if let Some(digit) = suffix.chars().next().filter(char::is_ascii_digit) {
+        let offset = token.beginning + digits_end;
+        let span = Span::new(token.file, offset, offset + 1, token.line, token.column + digits_end);
+        return Err(Diagnostic::error(
+            ErrorCode::InvalidIntegerLiteral,
+            span,
+            format!("Invalid digit {} in {} literal: {}", digit, base_name, text),
+        )
+        .with_label(token.span(), format!("{} literal", base_name)));
+    }
+    if digits.replace('_', "").is_empty() {
+        return Err(invalid(format!("Missing digits after the {} prefix: {}", &text[..prefix_len], text)));
+    }
+    if let Some(offset) = misplaced_underscore(digits, radix) {
+        return Err(underscore_error(token, source, ErrorCode::InvalidIntegerLiteral, prefix_len + offset, "integer"));
+    }
+    let digits = digits.replace('_', "");
+    if !suffix.is_empty() && integer_type(suffix).is_none() {
+        return Err(invalid(format!("Invalid suffix {} on integer literal: {}", suffix, text))
+            .with_help("the integer suffixes are i8, i16, i32, i64, u8, u16, u32 and u64"));
+    }
+    let value = u64::from_str_radix(&digits, radix)
+        .map_err(|_| invalid(format!("Integer literal is too large for any integer type: {}", text)))?;
+    let mut node = Node::from_integer(value as i128).with_span(token.span());
+    if !suffix.is_empty() {
+        node.type_name = Some(suffix.to_string());
+    }
+    Ok(node)
+}
 
-    while lex(source, &mut current_token).is_ok() {
-        *end = current_token.end;
-        let token_length = current_token.end - current_token.beginning;
-        if token_length == 0 {
+/// Parse a float literal, as in `3.14`, `.5`, `1e-9` or `2.5e3`, with an
+/// optional `f32` or `f64` suffix, as in `1.5f32`. Like an integer
+/// literal, an unsuffixed one takes its type from where it is used.
+pub fn parse_float(token: &Token, source: &str) -> Result<Node, Diagnostic> {
+    let text = token.text(source);
+    let invalid = |message: String| Diagnostic::error(ErrorCode::InvalidFloatLiteral, token.span(), message);
+    let bytes = text.as_bytes();
+    let mut digits_end = 0;
+    while let Some(&c) = bytes.get(digits_end) {
+        let sign = matches!(bytes.get(digits_end + 1), Some(b'+' | b'-')) as usize;
+        if c.is_ascii_digit() || c == b'_' || c == b'.' {
+            digits_end += 1;
+        } else if matches!(c, b'e' | b'E') && bytes.get(digits_end + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            digits_end += 1 + sign;
+        } else {
             break;
         }
+    }
+    let (digits, suffix) = (&text[..digits_end], &text[digits_end..]);
+    if let Some(offset) = misplaced_underscore(digits, 10) {
+        return Err(underscore_error(token, source, ErrorCode::InvalidFloatLiteral, offset, "float"));
+    }
+    if suffix.starts_with(['e', 'E']) {
+        return Err(invalid(format!("Missing digits in the exponent of float literal: {}", text)));
+    }
+    if !suffix.is_empty() && float_type(suffix).is_none() {
+        return Err(invalid(format!("Invalid suffix {} on float literal: {}", suffix, text))
+            .with_help("the float suffixes are f32 and f64"));
+    }
+    let value: f64 = digits
+        .replace('_', "")
+        .parse()
+        .map_err(|_| invalid(format!("Invalid float literal: {}", text)))?;
+    if value.is_infinite() {
+        return Err(invalid(format!("Float literal is too large for any float type: {}", text)));
+    }
+    let mut node = Node::from_float(value).with_span(token.span());
+    if !suffix.is_empty() {
+        node.type_name = Some(suffix.to_string());
+    }
+    Ok(node)
+}
+
+/// The span of `source[start..end]`, which lies within `token`.
+fn span_within(token: &Token, source: &str, start: usize, end: usize) -> Span {
+    let before = &source[token.beginning..start];
+    let (line, column) = match before.rfind('\n') {
+        Some(newline) => (token.line + before.matches('\n').count(), before[newline + 1..].chars().count() + 1),
+        None => (token.line, token.column + before.chars().count()),
+    };
+    Span::new(token.file, start, end, line, column)
+}
 
-        if let Ok(integer_node) = parse_integer(&current_token, source) {
-            result = integer_node;
-            current_token.beginning = current_token.end;
-            if lex(source, &mut current_token).is_err() {
-                return Ok(result);
+/// Decode the text between the quotes of a string or character literal,
+/// replacing each escape sequence with the character it stands for.
+fn unescape(token: &Token, source: &str) -> Result<String, Diagnostic> {
+    let body_start = token.beginning + 1;
+    let body = &source[body_start..token.end - 1];
+    let mut decoded = String::new();
+    let mut chars = body.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        let escape = chars.next().map(|(_, c)| c);
+        let unicode = if escape == Some('u') {
+            let rest = &body[start + 2..];
+            let digits = rest.strip_prefix('{').and_then(|rest| Some(&rest[..rest.find('}')?]));
+            if let Some(digits) = digits {
+                // The braces and digits are part of the escape.
+                for _ in 0..digits.chars().count() + 2 {
+                    chars.next();
+                }
             }
-            *end = current_token.end;
+            Some(digits)
         } else {
-            let symbol_node = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]);
-            current_token.beginning = current_token.end;
-            if lex(source, &mut current_token).is_err() {
-                return Ok(result);
+            None
+        };
+        let end = chars.peek().map_or(body.len(), |(end, _)| *end);
+        let escape_span = span_within(token, source, body_start + start, body_start + end);
+        let escaped = &body[start..end];
+        let invalid = |message: String| Diagnostic::error(ErrorCode::InvalidEscape, escape_span, message);
+        decoded.push(match (escape, unicode) {
+            (Some('n'), _) => '\n',
+            (Some('t'), _) => '\t',
+            (Some('r'), _) => '\r',
+            (Some('0'), _) => '\0',
+            (Some(c @ ('\\' | '"' | '\'')), _) => c,
+            (_, Some(None)) => {
+                return Err(invalid(format!("Unicode escape must be written as \\u{{...}}: {}", escaped))
+                    .with_help("put between one and six hexadecimal digits in braces, as in \\u{2603}"))
             }
-            *end = current_token.end;
-
-            if token_string_equalp(":", &current_token, source) {
-                current_token.beginning = current_token.end;
-                if lex(source, &mut current_token).is_err() {
-                    return Ok(result);
-                }
-                *end = current_token.end;
-
-                let expected_type_symbol = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]);
-                if let Some(_) = context.types.get(&expected_type_symbol) {
-                    let mut var_decl = Node::new(
-                        NodeType::VariableDeclaration,
-                        Some(NodeValue::VariableDeclaration {
-                            name: symbol_node.to_string(),
-                            var_type: expected_type_symbol.to_string(),
-                        }),
-                    );
-
-                    if lex(source, &mut current_token).is_ok() && token_string_equalp("=", &current_token, source) {
-                        current_token.beginning = current_token.end;
-                        if lex(source, &mut current_token).is_err() {
-                            return Ok(result);
-                        }
-                        *end = current_token.end;
-
-                        let value_node = parse_expr(context, source, end)?;
-                        var_decl.node_type = NodeType::VariableDeclarationInitialized;
-                        var_decl.add_child(value_node);
-                        result = var_decl;
-                    } else {
-                        result = var_decl;
-                    }
-                    return Ok(result);
-                } else {
-                    let err = format!(
-                        "Invalid type within variable declaration: {}",
-                        &source[current_token.beginning..current_token.end]
-                    );
-                    return Err(err);
+            (_, Some(Some(digits))) => {
+                let valid_digits = (1..=6).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_hexdigit());
+                if !valid_digits {
+                    return Err(invalid(format!("Unicode escape must have one to six hexadecimal digits: {}", escaped)));
                 }
+                let code = u32::from_str_radix(digits, 16).unwrap();
+                char::from_u32(code)
+                    .ok_or_else(|| invalid(format!("Unicode escape is not a valid character: {}", escaped))
+                        .with_note("surrogates, from D800 to DFFF, and values above 10FFFF are not characters"))?
             }
+            _ => {
+                return Err(invalid(format!("Unknown escape sequence: {}", escaped))
+                    .with_help("the escapes are \\n, \\t, \\r, \\0, \\\\, \\\", \\' and \\u{...}"))
+            }
+        });
+    }
+    Ok(decoded)
+}
+
+/// Parse a string literal, as in `"hello\n"`.
+pub fn parse_string(token: &Token, source: &str) -> Result<Node, Diagnostic> {
+    Ok(Node::from_string(&unescape(token, source)?).with_span(token.span()))
+}
 
-            if token_string_equalp(":=", &current_token, source) {
-                current_token.beginning = current_token.end;
-                if lex(source, &mut current_token).is_err() {
-                    return Ok(result);
+/// Parse a character literal, as in `'a'` or `'\u{2603}'`, which must hold
+/// exactly one character once its escape, if any, is decoded.
+pub fn parse_char(token: &Token, source: &str) -> Result<Node, Diagnostic> {
+    let decoded = unescape(token, source)?;
+    let mut chars = decoded.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Node::from_char(c).with_span(token.span())),
+        (None, _) => Err(Diagnostic::error(ErrorCode::InvalidCharLiteral, token.span(), "Empty character literal")),
+        (Some(_), Some(_)) => {
+            let text = token.text(source);
+            let string = format!("\"{}\"", &text[1..text.len() - 1]);
+            Err(Diagnostic::error(
+                ErrorCode::InvalidCharLiteral,
+                token.span(),
+                format!("Character literal must contain exactly one character: {}", text),
+            )
+            .with_suggestion(token.span(), string, "use double quotes for a string"))
+        }
+    }
+}
+
+/// Look at the token following `token` without consuming it.
+fn peek_token(source: &str, token: &Token) -> Result<Token, Diagnostic> {
+    let mut next = token.clone();
+    next_token(source, &mut next)?;
+    Ok(next)
+}
+
+/// Consume the next token, failing unless it is of the `expected` kind.
+/// A token of the wrong kind is left unconsumed for error recovery.
+fn expect_token(source: &str, token: &mut Token, expected: TokenKind, what: &str) -> Result<(), Diagnostic> {
+    let next = peek_token(source, token)?;
+    if next.kind != expected {
+        let mut err = unexpected_token(&next, source, what);
+        if let Some(text) = expected.text() {
+            err = err.with_suggestion(token.end_span(source), text, "insert the missing token");
+        }
+        return Err(err);
+    }
+    *token = next;
+    Ok(())
+}
+
+fn unexpected_token(token: &Token, source: &str, what: &str) -> Diagnostic {
+    let found = match token.kind {
+        TokenKind::EndOfFile => "end of file".to_string(),
+        _ => format!("{:?}: {}", token.kind, token.text(source)),
+    };
+    Diagnostic::error(ErrorCode::UnexpectedToken, token.span(), format!("Expected {} but got {}", what, found))
+}
+
+/// Check that `type_token` names a registered type, yielding the type it
+/// stands for. A struct stands for itself. `what` names where the type
+/// appears, for the error message; `void` is only accepted when
+/// `allow_void` is set, as it is for return types.
+fn check_type(context: &ParsingContext, source: &str, type_token: &Token, what: &str, allow_void: bool) -> Result<String, Diagnostic> {
+    let written = type_token.text(source);
+    let type_name = match context.types.get(&Node::from_symbol(written)).and_then(|node| node.value.as_ref()) {
+        Some(NodeValue::Symbol(type_name)) => type_name.clone(),
+        Some(NodeValue::StructDefinition { name, .. }) => name.clone(),
+        _ => {
+            return Err(Diagnostic::error(
+                ErrorCode::UnknownType,
+                type_token.span(),
+                format!("Invalid type within {}: {}", what, written),
+            ))
+        }
+    };
+    if type_name == VOID && !allow_void {
+        return Err(Diagnostic::error(
+            ErrorCode::VoidValue,
+            type_token.span(),
+            format!("Invalid type within {}: void has no values", what),
+        )
+        .with_help("void may only be used as the return type of a function"));
+    }
+    Ok(type_name)
+}
+
+/// Parse `( name : type, ... )`. A bad parameter type is recorded without
+/// abandoning the rest of the list; a malformed list is an error.
+fn parse_params(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Vec<Parameter>, Diagnostic> {
+    expect_token(source, token, TokenKind::LeftParen, "'('")?;
+    let open = token.span();
+    let mut params = Vec::new();
+    if peek_token(source, token)?.kind == TokenKind::RightParen {
+        next_token(source, token)?;
+        return Ok(params);
+    }
+    loop {
+        expect_token(source, token, TokenKind::Identifier, "parameter name")?;
+        let param_start = token.span();
+        let param_name = token.text(source).to_string();
+        expect_token(source, token, TokenKind::Colon, "':' after parameter name")?;
+        expect_token(source, token, TokenKind::Identifier, "parameter type")?;
+        let param_type = match check_type(context, source, token, "parameter list", false) {
+            Ok(param_type) => param_type,
+            Err(err) => {
+                context.diagnostics.push(err);
+                UNKNOWN.to_string()
+            }
+        };
+        params.push(Parameter { name: param_name, param_type, span: param_start.to(token.span()) });
+
+        let next = peek_token(source, token)?;
+        match next.kind {
+            TokenKind::RightParen => {
+                *token = next;
+                return Ok(params);
+            }
+            TokenKind::Comma => {
+                *token = next;
+                let comma = token.span();
+                if peek_token(source, token)?.kind == TokenKind::RightParen {
+                    return Err(Diagnostic::error(
+                        ErrorCode::TrailingComma,
+                        comma,
+                        "Trailing comma in parameter list",
+                    )
+                    .with_suggestion(comma, "", "remove the comma"));
                 }
-                *end = current_token.end;
-
-                let value_node = parse_expr(context, source, end)?;
-                let var_assign = Node::new(
-                    NodeType::VariableAssignment,
-                    Some(NodeValue::VariableAssignment {
-                        name: symbol_node.to_string(),
-                        value: Box::new(value_node),
-                    }),
+            }
+            _ => {
+                return Err(unexpected_token(&next, source, "',' or ')' after parameter")
+                    .with_label(open, "parameter list starts here")
+                    .with_suggestion(token.end_span(source), ")", "close the parameter list"));
+            }
+        }
+    }
+}
+
+/// Parse the optional parameter list and `: type` return type of a
+/// function, after its name.
+fn parse_signature(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<(Vec<Parameter>, String), Diagnostic> {
+    let params = if peek_token(source, token)?.kind == TokenKind::LeftParen {
+        parse_params(context, source, token)?
+    } else {
+        Vec::new()
+    };
+
+    let mut return_type = VOID.to_string();
+    if peek_token(source, token)?.kind == TokenKind::Colon {
+        next_token(source, token)?;
+        expect_token(source, token, TokenKind::Identifier, "return type")?;
+        return_type = match check_type(context, source, token, "function signature", true) {
+            Ok(return_type) => return_type,
+            Err(err) => {
+                context.diagnostics.push(err);
+                UNKNOWN.to_string()
+            }
+        };
+    }
+    Ok((params, return_type))
+}
+
+/// After a malformed function signature, skip the rest of it and the body
+/// that follows as one balanced `{ ... }` group, so that the body's `}` is
+/// not taken for the end of an enclosing block. Stops short of a `defun`,
+/// `struct` or `}` outside the group.
+fn skip_function_body(source: &str, token: &mut Token) {
+    let mut depth = 0usize;
+    loop {
+        let next = match peek_token(source, token) {
+            Ok(next) => next,
+            Err(err) if err.span.end > token.end => {
+                token.end = err.span.end;
+                continue;
+            }
+            Err(_) => {
+                token.end = source.len();
+                return;
+            }
+        };
+        match next.kind {
+            TokenKind::EndOfFile => return,
+            TokenKind::Defun | TokenKind::Struct | TokenKind::RightBrace if depth == 0 => return,
+            TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightBrace => depth -= 1,
+            _ => {}
+        }
+        *token = next;
+        if depth == 0 && token.kind == TokenKind::RightBrace {
+            return;
+        }
+    }
+}
+
+fn parse_defun(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let start = token.span();
+    expect_token(source, token, TokenKind::Identifier, "function name")?;
+    let name = token.text(source).to_string();
+
+    let (params, return_type) = match parse_signature(context, source, token) {
+        Ok(signature) => signature,
+        Err(err) => {
+            skip_function_body(source, token);
+            return Err(err);
+        }
+    };
+
+    let body = if peek_token(source, token)?.kind == TokenKind::LeftBrace {
+        parse_block(context, source, token, start, "function body")
+    } else {
+        Ok(Vec::new())
+    };
+
+    let definition = Node::new(
+        NodeType::FunctionDefinition,
+        Some(NodeValue::FunctionDefinition { name: name.clone(), params, return_type, body: body? }),
+    )
+    .with_span(start.to(token.span()));
+    if context.block_depth > 0 {
+        return Err(Diagnostic::error(
+            ErrorCode::NestedDefinition,
+            definition.span,
+            format!("Function {} must be defined at the top level", name),
+        ));
+    }
+    Ok(definition)
+}
+
+/// Parse `struct Name { field : type, ... }`, with `token` on the `struct`,
+/// and register the struct in `context.types`. A struct may only use types
+/// defined before it, so it can never contain itself. As in a parameter
+/// list, a bad field is recorded without abandoning the rest.
+fn parse_struct(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let start = token.span();
+    expect_token(source, token, TokenKind::Identifier, "struct name")?;
+    let name = token.text(source).to_string();
+    let name_span = token.span();
+    expect_token(source, token, TokenKind::LeftBrace, "'{' after struct name")?;
+    let open = token.span();
+
+    let mut fields: Vec<Field> = Vec::new();
+    loop {
+        let next = peek_token(source, token)?;
+        if next.kind == TokenKind::RightBrace {
+            *token = next;
+            break;
+        }
+        expect_token(source, token, TokenKind::Identifier, "field name or '}'")?;
+        let field_start = token.span();
+        let field_name = token.text(source).to_string();
+        expect_token(source, token, TokenKind::Colon, "':' after field name")?;
+        expect_token(source, token, TokenKind::Identifier, "field type")?;
+        let field_type = match check_type(context, source, token, "struct definition", false) {
+            Ok(field_type) => field_type,
+            Err(err) => {
+                context.diagnostics.push(err);
+                UNKNOWN.to_string()
+            }
+        };
+        let field = Field { name: field_name, field_type, span: field_start.to(token.span()) };
+        match fields.iter().find(|previous| previous.name == field.name) {
+            Some(previous) => context.diagnostics.push(
+                Diagnostic::error(
+                    ErrorCode::DuplicateDeclaration,
+                    field.span,
+                    format!("Field {} is already declared in struct {}", field.name, name),
+                )
+                .with_label(previous.span, "previously declared here"),
+            ),
+            None => fields.push(field),
+        }
+
+        let next = peek_token(source, token)?;
+        match next.kind {
+            TokenKind::Comma => *token = next,
+            TokenKind::RightBrace => {}
+            _ => {
+                return Err(unexpected_token(&next, source, "',' or '}' after field")
+                    .with_label(open, "struct body starts here")
+                    .with_suggestion(token.end_span(source), ",", "separate the fields with a comma"));
+            }
+        }
+    }
+
+    let definition = Node::new(NodeType::StructDefinition, Some(NodeValue::StructDefinition { name: name.clone(), fields }))
+        .with_span(start.to(token.span()));
+    if context.block_depth > 0 {
+        return Err(Diagnostic::error(
+            ErrorCode::NestedDefinition,
+            definition.span,
+            format!("Struct {} must be defined at the top level", name),
+        ));
+    }
+    let id = Node::from_symbol(&name);
+    match context.types.get(&id) {
+        Some(previous) => {
+            let err = Diagnostic::error(ErrorCode::DuplicateDeclaration, name_span, format!("Type {} is already defined", name));
+            context.diagnostics.push(match previous.node_type {
+                NodeType::StructDefinition => err.with_label(previous.span, "previously defined here"),
+                _ => err.with_note(format!("{} is a built-in type", name)),
+            });
+        }
+        None => context.types.set(&id, definition.clone()).unwrap(),
+    }
+    Ok(definition)
+}
+
+/// Parse a `{ ... }` block of statements. `owner` is the construct the
+/// block belongs to and `what` names the block, for reporting an
+/// unterminated block.
+fn parse_block(context: &mut ParsingContext, source: &str, token: &mut Token, owner: Span, what: &str) -> Result<Vec<Node>, Diagnostic> {
+    expect_token(source, token, TokenKind::LeftBrace, "'{'")?;
+    context.block_depth += 1;
+    let mut body = Vec::new();
+    let result = loop {
+        match peek_token(source, token) {
+            Ok(next) if next.kind == TokenKind::RightBrace => {
+                *token = next;
+                break Ok(body);
+            }
+            // An unterminated inner block has already been reported, and
+            // also accounts for this one.
+            Ok(next)
+                if next.kind == TokenKind::EndOfFile
+                    && context.diagnostics.last().is_some_and(|err| {
+                        err.code == ErrorCode::UnterminatedBlock && err.span == next.span()
+                    }) =>
+            {
+                break Ok(body);
+            }
+            Ok(next) if next.kind == TokenKind::EndOfFile => {
+                break Err(
+                    Diagnostic::error(ErrorCode::UnterminatedBlock, next.span(), format!("Unterminated {}", what))
+                        .with_label(owner, format!("{} starts here", what)),
                 );
+            }
+            _ => body.push(parse_or_recover(context, source, token)),
+        }
+    };
+    context.block_depth -= 1;
+    result
+}
+
+/// Parse `if cond { ... }` with an optional `else { ... }` or `else if`,
+/// with `token` on the `if`.
+fn parse_if(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let start = token.span();
+    let condition = parse_binary(context, source, token, 0)?;
+    let then_body = parse_block(context, source, token, start, "if block")?;
+    let mut else_body = None;
+    if peek_token(source, token)?.kind == TokenKind::Else {
+        next_token(source, token)?;
+        let else_start = token.span();
+        else_body = Some(if peek_token(source, token)?.kind == TokenKind::If {
+            next_token(source, token)?;
+            vec![parse_if(context, source, token)?]
+        } else {
+            parse_block(context, source, token, else_start, "else block")?
+        });
+    }
+    Ok(Node::new(
+        NodeType::If,
+        Some(NodeValue::If { condition: Box::new(condition), then_body, else_body }),
+    )
+    .with_span(start.to(token.span())))
+}
+
+/// The operator a token denotes in infix position along with its binding
+/// power. Higher binds tighter; every level is left-associative.
+fn binary_operator(kind: TokenKind) -> Option<(BinaryOperator, u8)> {
+    let operator = match kind {
+        TokenKind::PipePipe => (BinaryOperator::LogicalOr, 1),
+        TokenKind::AmpersandAmpersand => (BinaryOperator::LogicalAnd, 2),
+        TokenKind::Pipe => (BinaryOperator::BitOr, 3),
+        TokenKind::Caret => (BinaryOperator::BitXor, 4),
+        TokenKind::Ampersand => (BinaryOperator::BitAnd, 5),
+        TokenKind::EqualsEquals => (BinaryOperator::Equal, 6),
+        TokenKind::BangEquals => (BinaryOperator::NotEqual, 6),
+        TokenKind::Less => (BinaryOperator::Less, 7),
+        TokenKind::LessEquals => (BinaryOperator::LessEqual, 7),
+        TokenKind::Greater => (BinaryOperator::Greater, 7),
+        TokenKind::GreaterEquals => (BinaryOperator::GreaterEqual, 7),
+        TokenKind::LessLess => (BinaryOperator::ShiftLeft, 8),
+        TokenKind::GreaterGreater => (BinaryOperator::ShiftRight, 8),
+        TokenKind::Plus => (BinaryOperator::Add, 9),
+        TokenKind::Minus => (BinaryOperator::Subtract, 9),
+        TokenKind::Star => (BinaryOperator::Multiply, 10),
+        TokenKind::Slash => (BinaryOperator::Divide, 10),
+        TokenKind::Percent => (BinaryOperator::Modulo, 10),
+        _ => return None,
+    };
+    Some(operator)
+}
+
+fn unary_operator(kind: TokenKind) -> Option<UnaryOperator> {
+    match kind {
+        TokenKind::Minus => Some(UnaryOperator::Negate),
+        TokenKind::Bang => Some(UnaryOperator::Not),
+        _ => None,
+    }
+}
 
-                result = var_assign;
-                return Ok(result);
+fn parse_primary(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let next = peek_token(source, token)?;
+    let start = next.span();
+    match next.kind {
+        TokenKind::Integer => {
+            *token = next;
+            parse_integer(token, source)
+        }
+        TokenKind::Float => {
+            *token = next;
+            parse_float(token, source)
+        }
+        TokenKind::String => {
+            *token = next;
+            parse_string(token, source)
+        }
+        TokenKind::Char => {
+            *token = next;
+            parse_char(token, source)
+        }
+        TokenKind::True | TokenKind::False => {
+            *token = next;
+            Ok(Node::from_boolean(token.kind == TokenKind::True).with_span(start))
+        }
+        // A `(` on the same line as a name makes it a call; on a later
+        // line it starts a new statement. Likewise a `{` after the name of
+        // a struct makes a struct literal, while after any other name it
+        // opens a block, as in `while done { ... }`.
+        TokenKind::Identifier => {
+            *token = next;
+            let name = token.text(source).to_string();
+            let after = peek_token(source, token)?;
+            if after.kind == TokenKind::LeftParen && after.line == token.line {
+                let args = parse_args(context, source, token)?;
+                return Ok(Node::new(NodeType::FunctionCall, Some(NodeValue::FunctionCall { name, args }))
+                    .with_span(start.to(token.span())));
             }
+            let is_struct = context
+                .types
+                .get(&Node::from_symbol(&name))
+                .is_some_and(|definition| definition.node_type == NodeType::StructDefinition);
+            if after.kind == TokenKind::LeftBrace && after.line == token.line && is_struct {
+                let fields = parse_field_inits(context, source, token)?;
+                return Ok(Node::new(NodeType::StructLiteral, Some(NodeValue::StructLiteral { name, fields }))
+                    .with_span(start.to(token.span())));
+            }
+            Ok(Node::from_symbol(&name).with_span(start))
+        }
+        TokenKind::LeftParen => {
+            *token = next;
+            let inner = parse_binary(context, source, token, 0)?;
+            expect_token(source, token, TokenKind::RightParen, "')'")?;
+            Ok(inner.with_span(start.to(token.span())))
+        }
+        _ => Err(unexpected_token(&next, source, "an expression")),
+    }
+}
 
-            if token_string_equalp("defun", &current_token, source) {
-                current_token.beginning = current_token.end;
-                if lex(source, &mut current_token).is_err() {
-                    return Ok(result);
+/// Parse the `( expr, ... )` argument list of a call.
+fn parse_args(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Vec<Node>, Diagnostic> {
+    expect_token(source, token, TokenKind::LeftParen, "'('")?;
+    let open = token.span();
+    let mut args = Vec::new();
+    if peek_token(source, token)?.kind == TokenKind::RightParen {
+        next_token(source, token)?;
+        return Ok(args);
+    }
+    loop {
+        args.push(parse_binary(context, source, token, 0)?);
+        let next = peek_token(source, token)?;
+        match next.kind {
+            TokenKind::RightParen => {
+                *token = next;
+                return Ok(args);
+            }
+            TokenKind::Comma => {
+                *token = next;
+                let comma = token.span();
+                if peek_token(source, token)?.kind == TokenKind::RightParen {
+                    return Err(Diagnostic::error(
+                        ErrorCode::TrailingComma,
+                        comma,
+                        "Trailing comma in argument list",
+                    )
+                    .with_suggestion(comma, "", "remove the comma"));
                 }
-                *end = current_token.end;
+            }
+            _ => {
+                return Err(unexpected_token(&next, source, "',' or ')' after argument")
+                    .with_label(open, "argument list starts here")
+                    .with_suggestion(token.end_span(source), ")", "close the argument list"));
+            }
+        }
+    }
+}
 
-                if lex(source, &mut current_token).is_err() {
-                    return Ok(result);
-                }
-                let func_name_node = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]);
-                current_token.beginning = current_token.end;
-                if lex(source, &mut current_token).is_err() {
-                    return Ok(result);
+/// Parse the `{ field : expr, ... }` fields of a struct literal. Which
+/// fields the struct has is for the type checker to decide.
+fn parse_field_inits(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Vec<FieldInit>, Diagnostic> {
+    expect_token(source, token, TokenKind::LeftBrace, "'{'")?;
+    let open = token.span();
+    let mut fields = Vec::new();
+    loop {
+        let next = peek_token(source, token)?;
+        if next.kind == TokenKind::RightBrace {
+            *token = next;
+            return Ok(fields);
+        }
+        expect_token(source, token, TokenKind::Identifier, "field name or '}'")?;
+        let field_start = token.span();
+        let name = token.text(source).to_string();
+        expect_token(source, token, TokenKind::Colon, "':' after field name")?;
+        let value = parse_binary(context, source, token, 0)?;
+        let span = field_start.to(value.span);
+        fields.push(FieldInit { name, value, span });
+
+        let next = peek_token(source, token)?;
+        match next.kind {
+            TokenKind::Comma => *token = next,
+            TokenKind::RightBrace => {}
+            _ => {
+                return Err(unexpected_token(&next, source, "',' or '}' after field")
+                    .with_label(open, "struct literal starts here")
+                    .with_suggestion(token.end_span(source), ",", "separate the fields with a comma"));
+            }
+        }
+    }
+}
+
+/// Parse a primary expression followed by any number of `.field`
+/// accesses.
+fn parse_postfix(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let mut value = parse_primary(context, source, token)?;
+    while peek_token(source, token)?.kind == TokenKind::Dot {
+        next_token(source, token)?;
+        expect_token(source, token, TokenKind::Identifier, "field name after '.'")?;
+        let field = token.text(source).to_string();
+        let span = value.span.to(token.span());
+        value = Node::new(NodeType::FieldAccess, Some(NodeValue::FieldAccess { value: Box::new(value), field }))
+            .with_span(span);
+    }
+    Ok(value)
+}
+
+/// Whether `node` is a field of a variable, or of such a field, and so can
+/// be assigned to.
+fn is_assignable_field(node: &Node) -> bool {
+    match &node.value {
+        Some(NodeValue::FieldAccess { value, .. }) => {
+            value.node_type == NodeType::Symbol || is_assignable_field(value)
+        }
+        _ => false,
+    }
+}
+
+fn parse_unary(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let Some(operator) = unary_operator(peek_token(source, token)?.kind) else {
+        return parse_postfix(context, source, token);
+    };
+    next_token(source, token)?;
+    let start = token.span();
+    let operand = parse_unary(context, source, token)?;
+    let span = start.to(operand.span);
+    Ok(Node::new(
+        NodeType::UnaryOp,
+        Some(NodeValue::UnaryOp { operator, operand: Box::new(operand) }),
+    )
+    .with_span(span))
+}
+
+/// Parse a unary expression followed by any number of `as type` casts,
+/// which bind tighter than every binary operator but looser than the unary
+/// ones, so that `-a as f64` casts `-a`.
+fn parse_cast(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let mut value = parse_unary(context, source, token)?;
+    while peek_token(source, token)?.kind == TokenKind::As {
+        next_token(source, token)?;
+        expect_token(source, token, TokenKind::Identifier, "type name after `as`")?;
+        let target = check_type(context, source, token, "cast", false)?;
+        let span = value.span.to(token.span());
+        value = Node::new(NodeType::Cast, Some(NodeValue::Cast { value: Box::new(value), target })).with_span(span);
+    }
+    Ok(value)
+}
+
+/// Parse a binary expression by precedence climbing, consuming only
+/// operators that bind tighter than `min_precedence`.
+fn parse_binary(context: &mut ParsingContext, source: &str, token: &mut Token, min_precedence: u8) -> Result<Node, Diagnostic> {
+    let mut lhs = parse_cast(context, source, token)?;
+    while let Some((operator, precedence)) = binary_operator(peek_token(source, token)?.kind) {
+        if precedence <= min_precedence {
+            break;
+        }
+        next_token(source, token)?;
+        let rhs = parse_binary(context, source, token, precedence)?;
+        let span = lhs.span.to(rhs.span);
+        lhs = Node::new(
+            NodeType::BinaryOp,
+            Some(NodeValue::BinaryOp { operator, lhs: Box::new(lhs), rhs: Box::new(rhs) }),
+        )
+        .with_span(span);
+    }
+    Ok(lhs)
+}
+
+/// Parse the expression following `token`, leaving `token` on the last
+/// token consumed.
+pub fn parse_expr(context: &mut ParsingContext, source: &str, token: &mut Token) -> Result<Node, Diagnostic> {
+    let mut doc_lines = Vec::new();
+    while peek_token(source, token)?.kind == TokenKind::DocComment {
+        next_token(source, token)?;
+        let line = &token.text(source)["///".len()..];
+        doc_lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+    }
+
+    let next = peek_token(source, token)?;
+    let start = next.span();
+    let result = match next.kind {
+        TokenKind::EndOfFile => {
+            next_token(source, token)?;
+            Node::new(NodeType::None, None)
+        }
+        TokenKind::Defun => {
+            next_token(source, token)?;
+            parse_defun(context, source, token)?
+        }
+        TokenKind::Struct => {
+            next_token(source, token)?;
+            parse_struct(context, source, token)?
+        }
+        TokenKind::Return => {
+            next_token(source, token)?;
+            // A bare `return` ends its line, or the block.
+            let after = peek_token(source, token)?;
+            let value = if after.line > token.line || matches!(after.kind, TokenKind::RightBrace | TokenKind::EndOfFile) {
+                None
+            } else {
+                Some(Box::new(parse_binary(context, source, token, 0)?))
+            };
+            Node::new(NodeType::Return, Some(NodeValue::Return { value }))
+        }
+        TokenKind::If => {
+            next_token(source, token)?;
+            parse_if(context, source, token)?
+        }
+        TokenKind::While => {
+            next_token(source, token)?;
+            let condition = parse_binary(context, source, token, 0)?;
+            let body = parse_block(context, source, token, start, "while loop")?;
+            Node::new(NodeType::While, Some(NodeValue::While { condition: Box::new(condition), body }))
+        }
+        TokenKind::Break => {
+            next_token(source, token)?;
+            Node::new(NodeType::Break, None)
+        }
+        TokenKind::Continue => {
+            next_token(source, token)?;
+            Node::new(NodeType::Continue, None)
+        }
+        TokenKind::Identifier if peek_token(source, &next)?.kind == TokenKind::Colon => {
+            next_token(source, token)?;
+            let name = token.text(source).to_string();
+            next_token(source, token)?;
+            expect_token(source, token, TokenKind::Identifier, "type name")?;
+            let var_type = check_type(context, source, token, "variable declaration", false).unwrap_or_else(|err| {
+                context.diagnostics.push(err);
+                UNKNOWN.to_string()
+            });
+
+            let mut var_decl = Node::new(
+                NodeType::VariableDeclaration,
+                Some(NodeValue::VariableDeclaration { name, var_type }),
+            );
+            if peek_token(source, token)?.kind == TokenKind::Equals {
+                next_token(source, token)?;
+                let value_node = parse_binary(context, source, token, 0)?;
+                var_decl.node_type = NodeType::VariableDeclarationInitialized;
+                var_decl.add_child(value_node);
+            }
+            var_decl
+        }
+        TokenKind::Identifier if peek_token(source, &next)?.kind == TokenKind::ColonEquals => {
+            next_token(source, token)?;
+            let name = token.text(source).to_string();
+            next_token(source, token)?;
+            let value_node = parse_binary(context, source, token, 0)?;
+            Node::new(
+                NodeType::VariableAssignment,
+                Some(NodeValue::VariableAssignment { name, value: Box::new(value_node) }),
+            )
+        }
+        _ => {
+            let expr = parse_binary(context, source, token, 0)?;
+            if expr.node_type == NodeType::FieldAccess && peek_token(source, token)?.kind == TokenKind::ColonEquals {
+                if !is_assignable_field(&expr) {
+                    return Err(Diagnostic::error(
+                        ErrorCode::InvalidAssignmentTarget,
+                        expr.span,
+                        "Expected a variable or one of its fields before ':='",
+                    )
+                    .with_help("store the value in a variable before assigning to its fields"));
                 }
-                *end = current_token.end;
-
-                let mut params = Vec::new();
-                if token_string_equalp("(", &current_token, source) {
-                    current_token.beginning = current_token.end;
-                    while lex(source, &mut current_token).is_ok() && !token_string_equalp(")", &current_token, source) {
-                        let param_name = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]);
-                        current_token.beginning = current_token.end;
-
-                        if lex(source, &mut current_token).is_err() {
-                            return Ok(result);
-                        }
-                        *end = current_token.end;
-
-                        if token_string_equalp(":", &current_token, source) {
-                            current_token.beginning = current_token.end;
-                            if lex(source, &mut current_token).is_err() {
-                                return Ok(result);
-                            }
-                            *end = current_token.end;
-
-                            let param_type = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]);
-                            params.push((param_name.to_string(), param_type.to_string()));
-                        }
-
-                        current_token.beginning = current_token.end;
-                    }
-                    if lex(source, &mut current_token).is_err() {
-                        return Ok(result);
+                next_token(source, token)?;
+                let value_node = parse_binary(context, source, token, 0)?;
+                Node::new(
+                    NodeType::FieldAssignment,
+                    Some(NodeValue::FieldAssignment { target: Box::new(expr), value: Box::new(value_node) }),
+                )
+            } else {
+                expr
+            }
+        }
+    };
+
+    let mut result = result.with_span(start.to(token.span()));
+    if !doc_lines.is_empty() && result.is_declaration() {
+        result.doc = Some(doc_lines.join("\n"));
+    }
+    Ok(result)
+}
+
+/// Skip tokens until one that a statement can plausibly start at: the first
+/// token on a line after `line`, a `}`, a `defun` or `struct`, or the end of
+/// the file.
+fn synchronize(source: &str, token: &mut Token, line: usize) {
+    loop {
+        match peek_token(source, token) {
+            Ok(next) => match next.kind {
+                TokenKind::EndOfFile | TokenKind::RightBrace | TokenKind::Defun | TokenKind::Struct => return,
+                _ if next.line > line => return,
+                _ => *token = next,
+            },
+            Err(err) if err.span.end > token.end => token.end = err.span.end,
+            Err(_) => token.end = source.len(),
+        }
+    }
+}
+
+/// Parse a statement, or on a syntax error record the diagnostic, skip to
+/// the next synchronisation point and stand an `Error` node in its place.
+fn parse_or_recover(context: &mut ParsingContext, source: &str, token: &mut Token) -> Node {
+    let start = token.end;
+    match parse_expr(context, source, token) {
+        Ok(node) => node,
+        Err(err) => {
+            let span = err.span;
+            context.diagnostics.push(err);
+            synchronize(source, token, span.line);
+            if token.end == start {
+                if let Ok(next) = peek_token(source, token) {
+                    if !matches!(next.kind, TokenKind::EndOfFile | TokenKind::RightBrace) {
+                        *token = next;
                     }
-                    *end = current_token.end;
                 }
+            }
+            Node::new(NodeType::Error, None).with_span(span)
+        }
+    }
+}
 
-                let mut return_type = "void".to_string();
-                if token_string_equalp(":", &current_token, source) {
-                    current_token.beginning = current_token.end;
-                    if lex(source, &mut current_token).is_err() {
-                        return Ok(result);
-                    }
-                    *end = current_token.end;
+/// Parse every top-level form in `source` into a `Program` node. Only
+/// declarations, definitions and assignments may appear at the top level.
+/// Syntax errors are collected in `context.diagnostics` and leave `Error`
+/// nodes in the tree so that later passes can keep going.
+pub fn parse_program(context: &mut ParsingContext, source: &str, file: FileId) -> Node {
+    let mut token = Token::new(file);
+    let mut program = Node::new(NodeType::Program, None);
+    loop {
+        if let Ok(next) = peek_token(source, &token) {
+            if next.kind == TokenKind::RightBrace {
+                context
+                    .diagnostics
+                    .push(Diagnostic::error(ErrorCode::UnmatchedBrace, next.span(), "Unmatched '}'"));
+                token = next;
+                continue;
+            }
+        }
 
-                    return_type = Node::from_symbol_buffer(&source[current_token.beginning..current_token.end]).to_string();
+        let form = parse_or_recover(context, source, &mut token);
+        match form.node_type {
+            NodeType::None => break,
+            NodeType::Error => program.add_child(form),
+            _ if form.is_declaration()
+                || matches!(form.node_type, NodeType::VariableAssignment | NodeType::FieldAssignment) =>
+            {
+                program.add_child(form)
+            }
+            _ => {
+                context.diagnostics.push(Diagnostic::error(
+                    ErrorCode::ExpectedTopLevelForm,
+                    form.span,
+                    format!(
+                        "Expected a declaration or definition at the top level but got: {}",
+                        &source[form.span.start..form.span.end]
+                    ),
+                ));
+                program.add_child(Node::new(NodeType::Error, None).with_span(form.span));
+            }
+        }
+    }
+    program.with_span(Span::new(file, 0, source.len(), 1, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::{codes, compile};
+
+    /// Parse the single expression `source`, which must be valid.
+    fn expr(source: &str) -> Node {
+        let mut context = ParsingContext::new();
+        let node = parse_expr(&mut context, source, &mut Token::new(0)).unwrap();
+        assert!(context.diagnostics.is_empty(), "{:?}", context.diagnostics);
+        node
+    }
+
+    /// The shape of an expression tree, fully parenthesised in prefix form.
+    fn shape(node: &Node) -> String {
+        match &node.value {
+            Some(NodeValue::BinaryOp { operator, lhs, rhs }) => {
+                format!("({} {} {})", operator.symbol(), shape(lhs), shape(rhs))
+            }
+            Some(NodeValue::UnaryOp { operator, operand }) => format!("({} {})", operator.symbol(), shape(operand)),
+            Some(NodeValue::Cast { value, target }) => format!("(as {} {})", shape(value), target),
+            Some(NodeValue::Symbol(name)) => name.clone(),
+            Some(NodeValue::Integer(value)) => value.to_string(),
+            _ => node.to_string(),
+        }
+    }
+
+    /// The binary operators from the loosest binding to the tightest, those
+    /// of the same precedence together.
+    const PRECEDENCE: &[&[&str]] = &[
+        &["||"],
+        &["&&"],
+        &["|"],
+        &["^"],
+        &["&"],
+        &["==", "!="],
+        &["<", "<=", ">", ">="],
+        &["<<", ">>"],
+        &["+", "-"],
+        &["*", "/", "%"],
+    ];
+
+    #[test]
+    fn tighter_operators_bind_first() {
+        for (level, looser) in PRECEDENCE.iter().enumerate() {
+            for tighter in PRECEDENCE[level + 1..].iter() {
+                for (low, high) in looser.iter().flat_map(|low| tighter.iter().map(move |high| (low, high))) {
+                    let source = format!("a {} b {} c", low, high);
+                    assert_eq!(shape(&expr(&source)), format!("({} a ({} b c))", low, high), "{}", source);
+                    let source = format!("a {} b {} c", high, low);
+                    assert_eq!(shape(&expr(&source)), format!("({} ({} a b) c)", low, high), "{}", source);
                 }
+            }
+        }
+    }
 
-                let mut body = Vec::new();
-                if token_string_equalp("{", &current_token, source) {
-                    current_token.beginning = current_token.end;
-                    while lex(source, &mut current_token).is_ok() && !token_string_equalp("}", &current_token, source) {
-                        let stmt = parse_expr(context, source, end)?;
-                        body.push(stmt);
-                        current_token.beginning = current_token.end;
-                    }
+    #[test]
+    fn operators_of_one_precedence_associate_left() {
+        for operators in PRECEDENCE {
+            for first in operators.iter() {
+                for second in operators.iter() {
+                    let source = format!("a {} b {} c", first, second);
+                    assert_eq!(shape(&expr(&source)), format!("({} ({} a b) c)", second, first), "{}", source);
                 }
+            }
+        }
+        assert_eq!(shape(&expr("1 - 2 - 3")), "(- (- 1 2) 3)");
+        assert_eq!(shape(&expr("8 / 4 / 2")), "(/ (/ 8 4) 2)");
+    }
 
-                let func_def = Node::new(
-                    NodeType::FunctionDefinition,
-                    Some(NodeValue::FunctionDefinition {
-                        name: func_name_node.to_string(),
-                        params,
-                        return_type,
-                        body,
-                    }),
-                );
+    #[test]
+    fn unary_operators_and_casts_bind_tighter_than_binary_ones() {
+        assert_eq!(shape(&expr("-a as f64")), "(as (- a) f64)");
+        assert_eq!(shape(&expr("!a == b")), "(== (! a) b)");
+        assert_eq!(shape(&expr("- - a")), "(- (- a))");
+        assert_eq!(shape(&expr("a as i32 as f64 * b")), "(* (as (as a i32) f64) b)");
+        assert_eq!(shape(&expr("a - -b * c")), "(- a (* (- b) c))");
+    }
 
-                result = func_def;
-                return Ok(result);
-            }
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(shape(&expr("(a + b) * c")), "(* (+ a b) c)");
+        assert_eq!(shape(&expr("a - (b - c)")), "(- a (- b c))");
+        assert_eq!(shape(&expr("-(a + b) as f64")), "(as (- (+ a b)) f64)");
+        assert_eq!(shape(&expr("((a))")), "a");
+    }
 
-            println!("Unrecognized token: ");
-            crate::lexer::print_token(&current_token, source);
-            println!();
+    /// Lex the single number `source` and parse it as an integer literal.
+    fn integer(source: &str) -> Result<Node, Diagnostic> {
+        let mut token = Token::new(0);
+        next_token(source, &mut token).unwrap();
+        assert_eq!(token.kind, TokenKind::Integer, "{}", source);
+        parse_integer(&token, source)
+    }
 
-            return Err("Syntax error".to_string());
+    /// The value of the valid integer literal `source`.
+    fn integer_value(source: &str) -> i128 {
+        match &integer(source).unwrap().value {
+            Some(NodeValue::Integer(value)) => *value,
+            value => panic!("{} parsed as {:?}", source, value),
         }
+    }
 
-        println!("Intermediate node: ");
-        result.print(0);
-        println!();
+    /// The message and the primary span's columns of the invalid integer
+    /// literal `source`.
+    fn integer_error(source: &str) -> (String, usize, usize) {
+        let diagnostic = integer(source).unwrap_err();
+        assert_eq!(diagnostic.code, ErrorCode::InvalidIntegerLiteral, "{}", source);
+        let span = diagnostic.span;
+        (diagnostic.message, span.column, span.column + (span.end - span.start))
     }
 
-    Ok(result)
+    #[test]
+    fn integer_literals_take_a_base_prefix_and_separators() {
+        assert_eq!(integer_value("42"), 42);
+        assert_eq!(integer_value("0xff"), 255);
+        assert_eq!(integer_value("0xFF"), 255);
+        assert_eq!(integer_value("0o17"), 15);
+        assert_eq!(integer_value("0b1010"), 10);
+        assert_eq!(integer_value("1_000_000"), 1_000_000);
+        assert_eq!(integer_value("0b1111_0000"), 240);
+        assert_eq!(integer_value("18446744073709551615"), u64::MAX as i128);
+        assert_eq!(integer("0xffu8").unwrap().type_name.as_deref(), Some("u8"));
+    }
+
+    #[test]
+    fn an_invalid_digit_is_pointed_at() {
+        let (message, start, end) = integer_error("0b102");
+        assert_eq!(message, "Invalid digit 2 in binary literal: 0b102");
+        assert_eq!((start, end), (5, 6));
+        assert_eq!(integer_error("0o18").0, "Invalid digit 8 in octal literal: 0o18");
+    }
+
+    #[test]
+    fn malformed_integer_literals_are_rejected() {
+        assert_eq!(integer_error("0x").0, "Missing digits after the 0x prefix: 0x");
+        assert_eq!(integer_error("0b_").0, "Missing digits after the 0b prefix: 0b_");
+        assert_eq!(integer_error("12ab").0, "Invalid suffix ab on integer literal: 12ab");
+        assert_eq!(
+            integer_error("18446744073709551616").0,
+            "Integer literal is too large for any integer type: 18446744073709551616"
+        );
+    }
+
+    #[test]
+    fn an_underscore_must_separate_two_digits() {
+        assert_eq!(integer_error("1__0_"), ("Misplaced `_` in integer literal: 1__0_".to_string(), 2, 3));
+        assert_eq!(integer_error("1_").1, 2);
+        assert_eq!(integer_error("0x_ff").1, 3);
+        assert_eq!(integer_error("1_u8").1, 2);
+    }
+
+    /// Lex the single string or character literal `source` and parse it.
+    fn quoted(source: &str) -> Result<Node, Diagnostic> {
+        let mut token = Token::new(0);
+        next_token(source, &mut token).unwrap();
+        match token.kind {
+            TokenKind::String => parse_string(&token, source),
+            _ => parse_char(&token, source),
+        }
+    }
+
+    /// The error code and message of the invalid literal `source`.
+    fn quoted_error(source: &str) -> (ErrorCode, String) {
+        let diagnostic = quoted(source).unwrap_err();
+        (diagnostic.code, diagnostic.message)
+    }
+
+    #[test]
+    fn every_escape_is_decoded() {
+        let node = quoted(r#""\n\t\r\0\\\"\'\u{41}\u{2603}""#).unwrap();
+        assert!(matches!(&node.value, Some(NodeValue::String(s)) if s == "\n\t\r\0\\\"'A\u{2603}"));
+        let node = quoted(r"'\''").unwrap();
+        assert!(matches!(node.value, Some(NodeValue::Char('\''))));
+        let node = quoted(r"'\u{10FFFF}'").unwrap();
+        assert!(matches!(node.value, Some(NodeValue::Char('\u{10FFFF}'))));
+    }
+
+    #[test]
+    fn an_invalid_escape_is_pointed_at() {
+        let diagnostic = quoted(r#""ab\qc""#).unwrap_err();
+        assert_eq!(diagnostic.code, ErrorCode::InvalidEscape);
+        assert_eq!(diagnostic.message, r"Unknown escape sequence: \q");
+        assert_eq!((diagnostic.span.start, diagnostic.span.end, diagnostic.span.column), (3, 5, 4));
+        let diagnostic = quoted("\"a\nb\\u{d800}\"").unwrap_err();
+        assert_eq!((diagnostic.span.start, diagnostic.span.end), (4, 12));
+        assert_eq!((diagnostic.span.line, diagnostic.span.column), (2, 2));
+    }
+
+    #[test]
+    fn a_unicode_escape_needs_one_to_six_digits_of_a_character() {
+        let escape = |message: &str| (ErrorCode::InvalidEscape, message.to_string());
+        assert_eq!(quoted_error(r"'\u41'"), escape(r"Unicode escape must be written as \u{...}: \u"));
+        assert_eq!(quoted_error(r"'\u{}'"), escape(r"Unicode escape must have one to six hexadecimal digits: \u{}"));
+        assert_eq!(
+            quoted_error(r"'\u{1000000}'"),
+            escape(r"Unicode escape must have one to six hexadecimal digits: \u{1000000}")
+        );
+        assert_eq!(quoted_error(r"'\u{4g}'"), escape(r"Unicode escape must have one to six hexadecimal digits: \u{4g}"));
+        assert_eq!(quoted_error(r"'\u{110000}'"), escape(r"Unicode escape is not a valid character: \u{110000}"));
+        assert_eq!(quoted_error(r"'\u{D800}'"), escape(r"Unicode escape is not a valid character: \u{D800}"));
+        assert_eq!(quoted_error(r"'\u{DFFF}'"), escape(r"Unicode escape is not a valid character: \u{DFFF}"));
+    }
+
+    #[test]
+    fn a_character_literal_holds_exactly_one_character() {
+        assert_eq!(quoted_error("''"), (ErrorCode::InvalidCharLiteral, "Empty character literal".to_string()));
+        let diagnostic = quoted("'ab'").unwrap_err();
+        assert_eq!(diagnostic.code, ErrorCode::InvalidCharLiteral);
+        assert_eq!(diagnostic.message, "Character literal must contain exactly one character: 'ab'");
+        assert_eq!(diagnostic.suggestions[0].replacement, "\"ab\"");
+    }
+
+    #[test]
+    fn a_struct_and_its_fields_are_declared_once() {
+        assert_eq!(codes("struct P { a : integer }\nstruct P { b : integer }"), [ErrorCode::DuplicateDeclaration]);
+        assert_eq!(codes("struct P { a : integer, a : integer }"), [ErrorCode::DuplicateDeclaration]);
+    }
+
+    #[test]
+    fn only_a_variable_or_its_fields_can_be_assigned() {
+        let types = "struct P { a : integer }\ndefun mk () : P { P { a: 0 } }\n";
+        let source = |body: &str| format!("{}defun main () : integer {{ p : P = mk()\n {}\n 0 }}", types, body);
+        assert_eq!(codes(&source("p.a := 1")), []);
+        assert_eq!(codes(&source("mk().a := 1")), [ErrorCode::InvalidAssignmentTarget]);
+    }
+
+    #[test]
+    fn doc_comments_attach_to_the_next_declaration() {
+        let source = "/// Adds one.\n///   Indented.\n//// Not documentation.\n///\ndefun inc (x : integer) : integer { x + 1 }
+             /// The limit.\nlimit : integer = 10
+             defun main () : integer { inc(limit) }";
+        let (program, diagnostics) = compile(source, 0);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let docs: Vec<_> = program.iter_children().map(|form| form.doc.as_deref()).collect();
+        assert_eq!(docs, [Some("Adds one.\n  Indented.\n"), Some("The limit."), None]);
+        assert_eq!(expr("/// Not a declaration.\n1 + 2").doc, None);
+    }
+
+    #[test]
+    fn skips_the_body_of_a_malformed_signature() {
+        assert_eq!(codes("defun foo (x: integer,) : integer { x }"), [ErrorCode::TrailingComma]);
+        assert_eq!(codes("defun bar (x integer) : integer { x }"), [ErrorCode::UnexpectedToken]);
+        assert_eq!(codes("defun baz () : { if true { 1 } else { 2 } }\nx : integer = 1"), [ErrorCode::UnexpectedToken]);
+    }
+
+    #[test]
+    fn definitions_must_be_at_the_top_level() {
+        let source = "defun main () : integer {\n defun inner () : integer { return 5 }\n return 5\n}";
+        assert_eq!(codes(source), [ErrorCode::NestedDefinition]);
+        assert_eq!(codes("defun main () : integer { if true { struct P { a : integer } }\n 0 }"), [ErrorCode::NestedDefinition]);
+    }
+
+    #[test]
+    fn a_malformed_signature_does_not_swallow_the_next_definition() {
+        let source = "defun foo (x: integer\ndefun main () : integer { 0 }";
+        let (program, diagnostics) = compile(source, 0);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(program.iter_children().last().is_some_and(|form| form.node_type == NodeType::FunctionDefinition));
+    }
 }
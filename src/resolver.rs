@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use crate::environment::Environment;
+use crate::error::Diagnostic;
+use crate::error_codes::ErrorCode;
+use crate::node::{Node, NodeType, NodeValue};
+use crate::span::Span;
+
+/// Name resolution over a parsed `Program`. Variables (including
+/// parameters) live in `variables`, a scope chain that mirrors how the
+/// program runs; functions share one global namespace in `functions`.
+///
+/// Each scope binds a name to the node that declared it, so that
+/// diagnostics can point back at the declaration. Every function is
+/// declared before any body is resolved, so a call may precede the
+/// definition it refers to.
+pub struct Resolver {
+    variables: Environment,
+    functions: Environment,
+    /// The declaration each resolved use refers to, keyed by the byte
+    /// offset of the use.
+    pub bindings: HashMap<usize, Span>,
+    /// How many `while` loops enclose the node being resolved, within the
+    /// current function.
+    loop_depth: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            variables: Environment::new(None),
+            functions: Environment::new(None),
+            bindings: HashMap::new(),
+            loop_depth: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn resolve_program(&mut self, program: &Node) {
+        for form in program.iter_children() {
+            if let Some(NodeValue::FunctionDefinition { name, .. }) = &form.value {
+                self.declare_function(name, form.span);
+            }
+        }
+        for form in program.iter_children() {
+            self.resolve(form);
+        }
+    }
+
+    fn declare_function(&mut self, name: &str, span: Span) {
+        let id = Node::from_symbol(name);
+        if let Some(previous) = self.functions.get(&id) {
+            self.diagnostics.push(
+                Diagnostic::error(ErrorCode::DuplicateDeclaration, span, format!("Function {} is already defined", name))
+                    .with_label(previous.span, "previously defined here"),
+            );
+            return;
+        }
+        self.functions.set(&id, Node::from_symbol(name).with_span(span)).unwrap();
+    }
+
+    fn declare_variable(&mut self, name: &str, declaration: Node) {
+        let id = Node::from_symbol(name);
+        if let Some(previous) = self.variables.get_local(&id) {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    ErrorCode::DuplicateDeclaration,
+                    declaration.span,
+                    format!("{} is already declared in this scope", name),
+                )
+                .with_label(previous.span, "previously declared here"),
+            );
+            return;
+        }
+        self.variables.set(&id, declaration).unwrap();
+    }
+
+    fn use_variable(&mut self, name: &str, span: Span, what: &str) {
+        match self.variables.get(&Node::from_symbol(name)) {
+            Some(declaration) => {
+                self.bindings.insert(span.start, declaration.span);
+            }
+            None => self.diagnostics.push(Diagnostic::error(
+                ErrorCode::UndefinedVariable,
+                span,
+                format!("{} undeclared variable: {}", what, name),
+            )),
+        }
+    }
+
+    /// Resolve a block's statements in a scope of their own.
+    fn resolve_block(&mut self, body: &[Node]) {
+        self.variables.push_scope();
+        for stmt in body {
+            self.resolve(stmt);
+        }
+        self.variables.pop_scope();
+    }
+
+    fn resolve(&mut self, node: &Node) {
+        match &node.value {
+            Some(NodeValue::Symbol(name)) => self.use_variable(name, node.span, "Use of"),
+            Some(NodeValue::VariableDeclaration { name, .. }) => {
+                for child in node.iter_children() {
+                    self.resolve(child);
+                }
+                self.declare_variable(name, node.clone());
+            }
+            Some(NodeValue::VariableAssignment { name, value }) => {
+                self.resolve(value);
+                self.use_variable(name, node.span, "Assignment to");
+            }
+            Some(NodeValue::BinaryOp { lhs, rhs, .. }) => {
+                self.resolve(lhs);
+                self.resolve(rhs);
+            }
+            Some(NodeValue::UnaryOp { operand, .. }) => self.resolve(operand),
+            Some(NodeValue::Cast { value, .. }) => self.resolve(value),
+            Some(NodeValue::StructLiteral { fields, .. }) => {
+                for field in fields {
+                    self.resolve(&field.value);
+                }
+            }
+            Some(NodeValue::FieldAccess { value, .. }) => self.resolve(value),
+            Some(NodeValue::FieldAssignment { target, value }) => {
+                self.resolve(value);
+                self.resolve(target);
+            }
+            Some(NodeValue::FunctionCall { name, args }) => {
+                for arg in args {
+                    self.resolve(arg);
+                }
+                match self.functions.get(&Node::from_symbol(name)) {
+                    Some(definition) => {
+                        self.bindings.insert(node.span.start, definition.span);
+                    }
+                    None => self.diagnostics.push(Diagnostic::error(
+                        ErrorCode::UndefinedFunction,
+                        node.span,
+                        format!("Call to undefined function: {}", name),
+                    )),
+                }
+            }
+            Some(NodeValue::Return { value: Some(value) }) => self.resolve(value),
+            Some(NodeValue::If { condition, then_body, else_body }) => {
+                self.resolve(condition);
+                self.resolve_block(then_body);
+                if let Some(else_body) = else_body {
+                    self.resolve_block(else_body);
+                }
+            }
+            Some(NodeValue::While { condition, body }) => {
+                self.resolve(condition);
+                self.loop_depth += 1;
+                self.resolve_block(body);
+                self.loop_depth -= 1;
+            }
+            None if matches!(node.node_type, NodeType::Break | NodeType::Continue) && self.loop_depth == 0 => {
+                let keyword = if node.node_type == NodeType::Break { "break" } else { "continue" };
+                self.diagnostics.push(Diagnostic::error(
+                    ErrorCode::BreakOutsideLoop,
+                    node.span,
+                    format!("`{}` outside of a loop", keyword),
+                ));
+            }
+            Some(NodeValue::FunctionDefinition { params, body, .. }) => {
+                self.variables.push_scope();
+                for param in params {
+                    let param_id = Node::from_symbol(&param.name);
+                    if let Some(previous) = self.variables.get_local(&param_id) {
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                ErrorCode::DuplicateParameter,
+                                param.span,
+                                format!("Parameter {} is declared more than once", param.name),
+                            )
+                            .with_label(previous.span, "first declared here"),
+                        );
+                        continue;
+                    }
+                    let declaration = Node::new(
+                        NodeType::VariableDeclaration,
+                        Some(NodeValue::VariableDeclaration {
+                            name: param.name.clone(),
+                            var_type: param.param_type.clone(),
+                        }),
+                    )
+                    .with_span(param.span);
+                    self.variables.set(&param_id, declaration).unwrap();
+                }
+                // A loop around a definition does not extend into its body.
+                let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+                self.resolve_block(body);
+                self.loop_depth = enclosing_loop_depth;
+                self.variables.pop_scope();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_program, ParsingContext};
+
+    /// A resolver that has resolved `source`, which must parse cleanly.
+    fn resolve(source: &str) -> Resolver {
+        let mut context = ParsingContext::new();
+        let program = parse_program(&mut context, source, 0);
+        assert!(context.diagnostics.is_empty(), "{:?}", context.diagnostics);
+        let mut resolver = Resolver::new();
+        resolver.resolve_program(&program);
+        resolver
+    }
+
+    /// The line and column `span` starts at.
+    fn at(span: Span) -> (usize, usize) {
+        (span.line, span.column)
+    }
+
+    #[test]
+    fn a_duplicate_points_back_at_the_first_declaration() {
+        let source = "defun main () : integer {\n x : integer = 1\n x : integer = 2\n x\n}";
+        let diagnostics = resolve(source).diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        let err = &diagnostics[0];
+        assert_eq!(err.code, ErrorCode::DuplicateDeclaration);
+        assert_eq!(err.message, "x is already declared in this scope");
+        assert_eq!(at(err.span), (3, 2));
+        assert_eq!(err.labels[0].message, "previously declared here");
+        assert_eq!(at(err.labels[0].span), (2, 2));
+    }
+
+    #[test]
+    fn a_duplicate_parameter_points_back_at_the_first() {
+        let diagnostics = resolve("defun f (a : integer, b : integer, a : u8) : integer { a }").diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        let err = &diagnostics[0];
+        assert_eq!(err.code, ErrorCode::DuplicateParameter);
+        assert_eq!(err.message, "Parameter a is declared more than once");
+        assert_eq!(at(err.span), (1, 36));
+        assert_eq!(err.labels[0].message, "first declared here");
+        assert_eq!(at(err.labels[0].span), (1, 10));
+    }
+
+    #[test]
+    fn a_duplicate_function_points_back_at_the_first_definition() {
+        let diagnostics = resolve("defun f () : integer { 1 }\ndefun f () : integer { 2 }").diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::DuplicateDeclaration);
+        assert_eq!(at(diagnostics[0].span), (2, 1));
+        assert_eq!(at(diagnostics[0].labels[0].span), (1, 1));
+    }
+
+    #[test]
+    fn an_inner_block_may_shadow_an_outer_declaration() {
+        let source = "defun main () : integer {\n x : integer = 1\n if true {\n  x : integer = 2\n  x := x + 1\n }\n x\n}";
+        let resolver = resolve(source);
+        assert!(resolver.diagnostics.is_empty(), "{:?}", resolver.diagnostics);
+        let declared_at = |line: usize| {
+            let (offset, _) = source.match_indices('x').find(|(offset, _)| source[..*offset].matches('\n').count() + 1 == line).unwrap();
+            at(resolver.bindings[&offset])
+        };
+        assert_eq!(declared_at(5), (4, 3));
+        assert_eq!(declared_at(7), (2, 2));
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+pub type FileId = usize;
+
+/// A region of a source file. `line` and `column` are 1-based and refer to
+/// the first byte of the region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span { file, start, end, line, column }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        if other.start < self.start {
+            return other.to(*self);
+        }
+        Span { end: self.end.max(other.end), ..*self }
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::new(0, 0, 0, 1, 1)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, source: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile { name: name.to_string(), source, line_starts }
+    }
+
+    /// Convert a byte offset into a 1-based (line, column) pair.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.source[line_start..offset.min(self.source.len())].chars().count();
+        (line + 1, column + 1)
+    }
+
+    /// The text of the 1-based `line`, without its line terminator.
+    pub fn line_text(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\r', '\n'])
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add_file(&mut self, name: &str, source: String) -> FileId {
+        self.files.push(SourceFile::new(name, source));
+        self.files.len() - 1
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id]
+    }
+
+    pub fn line_column(&self, file: FileId, offset: usize) -> (usize, usize) {
+        self.file(file).line_column(offset)
+    }
+
+    /// Format the start of `span` as `name:line:column`.
+    pub fn location(&self, span: Span) -> String {
+        format!("{}:{}:{}", self.file(span.file).name, span.line, span.column)
+    }
+}
@@ -0,0 +1,768 @@
+use crate::environment::Environment;
+use crate::error::Diagnostic;
+use crate::error_codes::ErrorCode;
+use crate::node::{BinaryOperator, Field, FieldInit, Node, NodeType, NodeValue, Parameter, UnaryOperator};
+use crate::span::Span;
+
+pub const VOID: &str = "void";
+/// An alias for `DEFAULT_INTEGER`.
+pub const INTEGER: &str = "integer";
+pub const BOOLEAN: &str = "boolean";
+pub const CHAR: &str = "char";
+pub const STRING: &str = "string";
+/// The integer type an unsuffixed literal gets when nothing else decides
+/// it.
+pub const DEFAULT_INTEGER: &str = "i64";
+/// The type of an unsuffixed integer literal, or of arithmetic on such
+/// literals, until its context decides which integer type it has.
+pub const INTEGER_LITERAL: &str = "{integer}";
+/// The float type an unsuffixed float literal gets when nothing else
+/// decides it.
+pub const DEFAULT_FLOAT: &str = "f64";
+/// The type of an unsuffixed float literal, as `INTEGER_LITERAL` is for
+/// integers.
+pub const FLOAT_LITERAL: &str = "{float}";
+/// The type of a declaration whose written type does not exist. The parser
+/// reports the name once; nothing checked against this type is reported
+/// again.
+pub const UNKNOWN: &str = "{unknown}";
+
+/// Every sized integer type, with its width in bits and whether it is
+/// signed.
+pub const INTEGER_TYPES: &[(&str, u32, bool)] = &[
+    ("i8", 8, true),
+    ("i16", 16, true),
+    ("i32", 32, true),
+    ("i64", 64, true),
+    ("u8", 8, false),
+    ("u16", 16, false),
+    ("u32", 32, false),
+    ("u64", 64, false),
+];
+
+/// The width in bits and signedness of a sized integer type.
+pub fn integer_type(type_name: &str) -> Option<(u32, bool)> {
+    INTEGER_TYPES.iter().find(|(name, ..)| *name == type_name).map(|&(_, bits, signed)| (bits, signed))
+}
+
+/// The smallest and largest values of a sized integer type.
+pub fn integer_range(type_name: &str) -> Option<(i128, i128)> {
+    let (bits, signed) = integer_type(type_name)?;
+    Some(if signed { (-(1 << (bits - 1)), (1 << (bits - 1)) - 1) } else { (0, (1 << bits) - 1) })
+}
+
+/// Every floating-point type, with its width in bits.
+pub const FLOAT_TYPES: &[(&str, u32)] = &[("f32", 32), ("f64", 64)];
+
+/// The width in bits of a floating-point type.
+pub fn float_type(type_name: &str) -> Option<u32> {
+    FLOAT_TYPES.iter().find(|(name, _)| *name == type_name).map(|&(_, bits)| bits)
+}
+
+fn is_integer(type_name: &str) -> bool {
+    type_name == INTEGER_LITERAL || integer_type(type_name).is_some()
+}
+
+fn is_float(type_name: &str) -> bool {
+    type_name == FLOAT_LITERAL || float_type(type_name).is_some()
+}
+
+/// Whether a literal of type `literal`, such as `INTEGER_LITERAL`, may take
+/// the type `type_name`.
+fn literal_fits(literal: &str, type_name: &str) -> bool {
+    match literal {
+        INTEGER_LITERAL => integer_type(type_name).is_some(),
+        FLOAT_LITERAL => float_type(type_name).is_some(),
+        _ => false,
+    }
+}
+
+/// `type_name`, unless it is `UNKNOWN`.
+fn known(type_name: &str) -> Option<String> {
+    (type_name != UNKNOWN).then(|| type_name.to_string())
+}
+
+/// The type a literal of type `literal` gets when nothing else decides it.
+fn literal_default(literal: &str) -> Option<&'static str> {
+    match literal {
+        INTEGER_LITERAL => Some(DEFAULT_INTEGER),
+        FLOAT_LITERAL => Some(DEFAULT_FLOAT),
+        _ => None,
+    }
+}
+
+/// Static type checking over a resolved `Program`. Types are named by
+/// strings, just as in declarations. Checking annotates every expression
+/// node with its `type_name`, turning the tree into a typed AST.
+///
+/// An expression whose type cannot be known, for example a use of an
+/// undeclared variable that the resolver already reported, has no type and
+/// is never reported again.
+///
+/// An unsuffixed literal takes its type from where it is used, as in
+/// `a : u8 = 200` or `b : f32 = 1.5`, and is range checked once that type
+/// is settled. Integers and floats never mix: converting between them takes
+/// an `as` cast.
+pub struct TypeChecker {
+    /// Maps each variable in scope to a symbol node naming its type.
+    variables: Environment,
+    /// Maps each function to its definition, for its signature.
+    functions: Environment,
+    /// Maps each struct to its definition, for its fields.
+    structs: Environment,
+    /// The return type of the function whose body is being checked.
+    return_type: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            variables: Environment::new(None),
+            functions: Environment::new(None),
+            structs: Environment::new(None),
+            return_type: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn check_program(&mut self, program: &mut Node) {
+        // Signatures and structs first, so that a call may precede its
+        // definition.
+        for form in program.iter_children() {
+            let (definitions, name) = match &form.value {
+                Some(NodeValue::FunctionDefinition { name, .. }) => (&mut self.functions, name),
+                Some(NodeValue::StructDefinition { name, .. }) => (&mut self.structs, name),
+                _ => continue,
+            };
+            let id = Node::from_symbol(name);
+            if definitions.get(&id).is_none() {
+                definitions.set(&id, form.clone()).unwrap();
+            }
+        }
+        for form in program.children.iter_mut() {
+            let mut form = Some(form);
+            while let Some(node) = form {
+                self.check(node);
+                form = node.next_child.as_deref_mut();
+            }
+        }
+    }
+
+    fn variable_type(&self, name: &str) -> Option<String> {
+        match &self.variables.get(&Node::from_symbol(name))?.value {
+            Some(NodeValue::Symbol(type_name)) => known(type_name),
+            _ => None,
+        }
+    }
+
+    /// The fields of the struct `type_name`, if it is one.
+    fn struct_fields(&self, type_name: &str) -> Option<Vec<Field>> {
+        match &self.structs.get(&Node::from_symbol(type_name))?.value {
+            Some(NodeValue::StructDefinition { fields, .. }) => Some(fields.clone()),
+            _ => None,
+        }
+    }
+
+    /// The type of the field `field` of a value of type `type_name`,
+    /// reporting a field that does not exist.
+    fn field_type(&mut self, type_name: &str, field: &str, span: Span) -> Option<String> {
+        let Some(fields) = self.struct_fields(type_name) else {
+            self.diagnostics.push(
+                Diagnostic::error(ErrorCode::UnknownField, span, format!("Type {} has no field {}", type_name, field))
+                    .with_note("only structs have fields"),
+            );
+            return None;
+        };
+        match fields.iter().find(|candidate| candidate.name == field) {
+            Some(found) => known(&found.field_type),
+            None => {
+                self.diagnostics.push(
+                    Diagnostic::error(ErrorCode::UnknownField, span, format!("Struct {} has no field {}", type_name, field))
+                        .with_help(describe_fields(type_name, &fields)),
+                );
+                None
+            }
+        }
+    }
+
+    /// Check the fields of a literal of the struct `name` against its
+    /// definition, then put them in the order of the definition.
+    fn check_struct_literal(&mut self, name: &str, fields: &mut [FieldInit], span: Span) {
+        for field in fields.iter_mut() {
+            self.check(&mut field.value);
+        }
+        let Some(definition) = self.struct_fields(name) else {
+            return;
+        };
+        for field in fields.iter_mut() {
+            match definition.iter().find(|expected| expected.name == field.name) {
+                None => {
+                    self.diagnostics.push(
+                        Diagnostic::error(ErrorCode::UnknownField, field.span, format!("Struct {} has no field {}", name, field.name))
+                            .with_help(describe_fields(name, &definition)),
+                    );
+                    self.settle_default(&mut field.value);
+                }
+                Some(expected) => self.expect_value(&expected.field_type, &mut field.value, "field initializer"),
+            }
+        }
+        let mut seen: Vec<&FieldInit> = Vec::new();
+        for field in fields.iter() {
+            match seen.iter().find(|previous| previous.name == field.name) {
+                Some(previous) => self.diagnostics.push(
+                    Diagnostic::error(
+                        ErrorCode::DuplicateDeclaration,
+                        field.span,
+                        format!("Field {} is already initialized", field.name),
+                    )
+                    .with_label(previous.span, "previously initialized here"),
+                ),
+                None => seen.push(field),
+            }
+        }
+        let missing: Vec<&str> = definition
+            .iter()
+            .filter(|expected| !fields.iter().any(|field| field.name == expected.name))
+            .map(|expected| expected.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.diagnostics.push(Diagnostic::error(
+                ErrorCode::MissingField,
+                span,
+                format!("Missing fields in literal of struct {}: {}", name, missing.join(", ")),
+            ));
+        }
+        fields.sort_by_key(|field| definition.iter().position(|expected| expected.name == field.name));
+    }
+
+    /// Report a mismatch unless either type is unknown.
+    fn expect_type(&mut self, expected: &str, found: &Option<String>, span: Span, context: &str) {
+        if let Some(found) = found {
+            if found != expected && expected != UNKNOWN {
+                self.diagnostics.push(Diagnostic::error(
+                    ErrorCode::TypeMismatch,
+                    span,
+                    format!("Mismatched types in {}: expected {} but found {}", context, expected, found),
+                ));
+            }
+        }
+    }
+
+    /// Report a mismatch unless `found` is an integer type, or unknown.
+    /// Yields whether it was reported.
+    fn expect_integer(&mut self, found: &Option<String>, span: Span, context: &str) -> bool {
+        let mismatched = found.as_deref().is_some_and(|found| !is_integer(found));
+        if mismatched {
+            self.expect_type(INTEGER, found, span, context);
+        }
+        mismatched
+    }
+
+    /// Report a mismatch unless `found` is an integer or float type, or
+    /// unknown. Yields whether it was reported.
+    fn expect_number(&mut self, found: &Option<String>, span: Span, context: &str) -> bool {
+        let mismatched = found.as_deref().is_some_and(|found| !is_integer(found) && !is_float(found));
+        if mismatched {
+            self.expect_type("integer or float", found, span, context);
+        }
+        mismatched
+    }
+
+    /// `unify_operands`, unless an operand was already reported as being
+    /// of the wrong kind, in which case the type is unknown.
+    fn unify_checked_operands(&mut self, mismatched: bool, lhs: &mut Node, rhs: &mut Node, default: bool, context: &str) -> Option<String> {
+        if mismatched {
+            self.settle_default(lhs);
+            self.settle_default(rhs);
+            return None;
+        }
+        self.unify_operands(lhs, rhs, default, context)
+    }
+
+    /// Check that `node`, already checked, has the `expected` type. A
+    /// literal expected to be of a type of its kind takes that type.
+    fn expect_value(&mut self, expected: &str, node: &mut Node, context: &str) {
+        if node.type_name.as_deref().is_some_and(|literal| literal_fits(literal, expected)) {
+            self.settle_literal(node, expected);
+        } else {
+            self.expect_type(expected, &node.type_name.clone(), node.span, context);
+        }
+    }
+
+    /// Give a literal in `node` that nothing else decided its default type.
+    fn settle_default(&mut self, node: &mut Node) {
+        if let Some(default) = node.type_name.as_deref().and_then(literal_default) {
+            self.settle_literal(node, default);
+        }
+    }
+
+    /// Give the literals in `node` the type `type_name`, range checking
+    /// each one. Nodes of any other type, or literals of another kind, are
+    /// left alone.
+    fn settle_literal(&mut self, node: &mut Node, type_name: &str) {
+        if !node.type_name.as_deref().is_some_and(|literal| literal_fits(literal, type_name)) {
+            return;
+        }
+        node.type_name = Some(type_name.to_string());
+        match &mut node.value {
+            Some(NodeValue::Integer(value)) => self.check_literal(*value, type_name, node.span),
+            Some(NodeValue::Float(value)) => self.check_float_literal(value, type_name, node.span),
+            Some(NodeValue::UnaryOp { operator: UnaryOperator::Negate, operand })
+                if operand.node_type == NodeType::Integer =>
+            {
+                operand.type_name = Some(type_name.to_string());
+                if let Some(NodeValue::Integer(value)) = operand.value {
+                    self.check_literal(-value, type_name, node.span);
+                }
+            }
+            Some(NodeValue::UnaryOp { operator, operand }) => {
+                self.settle_literal(operand, type_name);
+                if *operator == UnaryOperator::Negate {
+                    self.check_negate(type_name, node.span);
+                }
+            }
+            Some(NodeValue::BinaryOp { operator, lhs, rhs }) => {
+                self.settle_literal(lhs, type_name);
+                // A shift amount was settled on its own.
+                if !matches!(operator, BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight) {
+                    self.settle_literal(rhs, type_name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Report a literal that does not fit in its integer type.
+    fn check_literal(&mut self, value: i128, type_name: &str, span: Span) {
+        let Some((min, max)) = integer_range(type_name) else {
+            return;
+        };
+        if value < min || value > max {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    ErrorCode::IntegerOutOfRange,
+                    span,
+                    format!("Integer literal out of range for {}: {}", type_name, value),
+                )
+                .with_note(format!("{} ranges from {} to {}", type_name, min, max)),
+            );
+        }
+    }
+
+    /// Round a float literal of type `f32` to its precision, reporting one
+    /// too large for it. Every finite `f64` is already in range.
+    fn check_float_literal(&mut self, value: &mut f64, type_name: &str, span: Span) {
+        if float_type(type_name) != Some(32) {
+            return;
+        }
+        if (*value as f32).is_finite() {
+            *value = *value as f32 as f64;
+        } else {
+            self.diagnostics.push(
+                Diagnostic::error(
+                    ErrorCode::FloatOutOfRange,
+                    span,
+                    format!("Float literal out of range for {}: {:e}", type_name, value),
+                )
+                .with_note(format!("{} ranges from {:e} to {:e}", type_name, f32::MIN, f32::MAX)),
+            );
+        }
+    }
+
+    /// Report negation of an unsigned type.
+    fn check_negate(&mut self, type_name: &str, span: Span) {
+        if integer_type(type_name).is_some_and(|(_, signed)| !signed) {
+            self.diagnostics.push(Diagnostic::error(
+                ErrorCode::UnsignedNegation,
+                span,
+                format!("Cannot negate a value of unsigned type {}", type_name),
+            ));
+        }
+    }
+
+    /// Settle the types of two operands that must match: a literal takes
+    /// the type of the other side, and when both sides are literals of the
+    /// same kind they stay literals unless `default` asks for the default
+    /// type. Operands that do not match are reported, and leave the type
+    /// unknown.
+    fn unify_operands(&mut self, lhs: &mut Node, rhs: &mut Node, default: bool, context: &str) -> Option<String> {
+        let (lhs_type, rhs_type) = (lhs.type_name.clone()?, rhs.type_name.clone()?);
+        if let (Some(lhs_default), true) = (literal_default(&lhs_type), lhs_type == rhs_type) {
+            if !default {
+                return Some(lhs_type);
+            }
+            self.settle_literal(lhs, lhs_default);
+            self.settle_literal(rhs, lhs_default);
+            return Some(lhs_default.to_string());
+        }
+        if literal_fits(&lhs_type, &rhs_type) {
+            self.expect_value(&rhs_type, lhs, context);
+            return Some(rhs_type);
+        }
+        self.expect_value(&lhs_type, rhs, context);
+        (rhs.type_name.as_deref() == Some(lhs_type.as_str())).then_some(lhs_type)
+    }
+
+    /// Report a signature that `main` cannot be run with: it is called
+    /// with no arguments, and its result is the exit status.
+    fn check_main(&mut self, params: &[Parameter], return_type: &str, span: Span) {
+        if let (Some(first), Some(last)) = (params.first(), params.last()) {
+            self.diagnostics.push(Diagnostic::error(
+                ErrorCode::InvalidMain,
+                first.span.to(last.span),
+                "main must not take any parameters",
+            ));
+        }
+        if return_type != VOID && return_type != UNKNOWN && integer_type(return_type).is_none() {
+            self.diagnostics.push(
+                Diagnostic::error(ErrorCode::InvalidMain, span, format!("main must not return {}", return_type))
+                    .with_help("return an integer type, which becomes the exit status, or void"),
+            );
+        }
+    }
+
+    /// Check a block's statements in a scope of their own.
+    fn check_block(&mut self, body: &mut [Node]) {
+        self.variables.push_scope();
+        for stmt in body.iter_mut() {
+            self.check(stmt);
+            self.settle_default(stmt);
+        }
+        self.variables.pop_scope();
+    }
+
+    /// Check `node` and record its type. Statements have type `void`.
+    fn check(&mut self, node: &mut Node) -> Option<String> {
+        let span = node.span;
+        let type_name = match &mut node.value {
+            // A suffixed literal was given its type by the parser.
+            Some(NodeValue::Integer(value)) => match node.type_name.clone() {
+                Some(type_name) => {
+                    self.check_literal(*value, &type_name, span);
+                    Some(type_name)
+                }
+                None => Some(INTEGER_LITERAL.to_string()),
+            },
+            Some(NodeValue::Float(value)) => match node.type_name.clone() {
+                Some(type_name) => {
+                    self.check_float_literal(value, &type_name, span);
+                    Some(type_name)
+                }
+                None => Some(FLOAT_LITERAL.to_string()),
+            },
+            Some(NodeValue::Boolean(_)) => Some(BOOLEAN.to_string()),
+            Some(NodeValue::String(_)) => Some(STRING.to_string()),
+            Some(NodeValue::Char(_)) => Some(CHAR.to_string()),
+            Some(NodeValue::Symbol(name)) => self.variable_type(name),
+            Some(NodeValue::VariableDeclaration { name, var_type }) => {
+                let (name, var_type) = (name.clone(), var_type.clone());
+                if let Some(initializer) = node.children.first_mut() {
+                    self.check(initializer);
+                    self.expect_value(&var_type, initializer, "initializer");
+                }
+                self.variables.set(&Node::from_symbol(&name), Node::from_symbol(&var_type)).unwrap();
+                Some(VOID.to_string())
+            }
+            Some(NodeValue::VariableAssignment { name, value }) => {
+                self.check(value);
+                match self.variable_type(name) {
+                    Some(var_type) => self.expect_value(&var_type, value, "assignment"),
+                    None => self.settle_default(value),
+                }
+                Some(VOID.to_string())
+            }
+            Some(NodeValue::BinaryOp { operator, lhs, rhs }) => {
+                let lhs_type = self.check(lhs);
+                let rhs_type = self.check(rhs);
+                let context = format!("operand of `{}`", operator.symbol());
+                match operator {
+                    _ if operator.is_logical() => {
+                        self.expect_type(BOOLEAN, &lhs_type, lhs.span, &context);
+                        self.expect_type(BOOLEAN, &rhs_type, rhs.span, &context);
+                        Some(BOOLEAN.to_string())
+                    }
+                    // Either side may be of any type, as long as both match.
+                    BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                        self.unify_operands(lhs, rhs, true, &context);
+                        Some(BOOLEAN.to_string())
+                    }
+                    // The amount may be of any integer type.
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => {
+                        self.expect_integer(&lhs_type, lhs.span, &context);
+                        self.expect_integer(&rhs_type, rhs.span, &context);
+                        self.settle_default(rhs);
+                        lhs_type
+                    }
+                    BinaryOperator::BitAnd | BinaryOperator::BitOr | BinaryOperator::BitXor => {
+                        let mismatched = self.expect_integer(&lhs_type, lhs.span, &context)
+                            | self.expect_integer(&rhs_type, rhs.span, &context);
+                        self.unify_checked_operands(mismatched, lhs, rhs, false, &context)
+                    }
+                    _ => {
+                        let mismatched = self.expect_number(&lhs_type, lhs.span, &context)
+                            | self.expect_number(&rhs_type, rhs.span, &context);
+                        let operand_type =
+                            self.unify_checked_operands(mismatched, lhs, rhs, operator.is_comparison(), &context);
+                        if operator.is_comparison() {
+                            Some(BOOLEAN.to_string())
+                        } else {
+                            operand_type
+                        }
+                    }
+                }
+            }
+            // `-128i8` is in range even though `128i8` is not, so a negated
+            // literal is checked as a whole.
+            Some(NodeValue::UnaryOp { operator: UnaryOperator::Negate, operand }) if operand.node_type == NodeType::Integer => {
+                let type_name = operand.type_name.clone().unwrap_or(INTEGER_LITERAL.to_string());
+                if let (Some(NodeValue::Integer(value)), false) = (&operand.value, type_name == INTEGER_LITERAL) {
+                    self.check_literal(-value, &type_name, span);
+                }
+                operand.type_name = Some(type_name.clone());
+                Some(type_name)
+            }
+            Some(NodeValue::UnaryOp { operator, operand }) => {
+                let operand_type = self.check(operand);
+                let context = format!("operand of `{}`", operator.symbol());
+                match operator {
+                    UnaryOperator::Negate => {
+                        self.expect_number(&operand_type, operand.span, &context);
+                        if let Some(operand_type) = &operand_type {
+                            self.check_negate(operand_type, span);
+                        }
+                        operand_type
+                    }
+                    UnaryOperator::Not => {
+                        self.expect_type(BOOLEAN, &operand_type, operand.span, &context);
+                        Some(BOOLEAN.to_string())
+                    }
+                }
+            }
+            // A literal is cast from its default type, so `300 as u8` wraps.
+            Some(NodeValue::Cast { value, target }) => {
+                self.check(value);
+                self.settle_default(value);
+                if let Some(value_type) = value.type_name.clone() {
+                    let number = |type_name: &str| is_integer(type_name) || is_float(type_name);
+                    let allowed = value_type == *target
+                        || (number(&value_type) && number(target))
+                        || ((value_type == BOOLEAN || value_type == CHAR) && is_integer(target));
+                    if !allowed {
+                        self.diagnostics.push(Diagnostic::error(
+                            ErrorCode::InvalidCast,
+                            span,
+                            format!("Cannot cast {} to {}", value_type, target),
+                        ));
+                    }
+                }
+                Some(target.clone())
+            }
+            Some(NodeValue::StructDefinition { .. }) => Some(VOID.to_string()),
+            Some(NodeValue::StructLiteral { name, fields }) => {
+                let name = name.clone();
+                self.check_struct_literal(&name, fields, span);
+                Some(name)
+            }
+            Some(NodeValue::FieldAccess { value, field }) => {
+                self.check(value);
+                self.settle_default(value);
+                let value_type = value.type_name.clone();
+                value_type.and_then(|value_type| self.field_type(&value_type, field, span))
+            }
+            Some(NodeValue::FieldAssignment { target, value }) => {
+                let target_type = self.check(target);
+                self.check(value);
+                match target_type {
+                    Some(target_type) => self.expect_value(&target_type, value, "assignment"),
+                    None => self.settle_default(value),
+                }
+                Some(VOID.to_string())
+            }
+            Some(NodeValue::FunctionCall { name, args }) => {
+                for arg in args.iter_mut() {
+                    self.check(arg);
+                }
+                let definition = self.functions.get(&Node::from_symbol(name)).cloned();
+                match definition.as_ref().map(|definition| (&definition.value, definition.span)) {
+                    Some((Some(NodeValue::FunctionDefinition { params, return_type, .. }), definition_span)) => {
+                        if params.len() != args.len() {
+                            self.diagnostics.push(
+                                Diagnostic::error(
+                                    ErrorCode::ArgumentCount,
+                                    span,
+                                    format!("{} takes {} argument(s) but {} were given", name, params.len(), args.len()),
+                                )
+                                .with_label(definition_span, "function defined here"),
+                            );
+                        } else {
+                            for (param, arg) in params.iter().zip(args.iter_mut()) {
+                                self.expect_value(&param.param_type, arg, "argument");
+                            }
+                        }
+                        known(return_type)
+                    }
+                    // An undefined function was already reported by the resolver.
+                    _ => None,
+                }
+            }
+            Some(NodeValue::Return { value }) => {
+                let return_type = self.return_type.clone().unwrap_or(VOID.to_string());
+                match value {
+                    Some(value) => {
+                        self.check(value);
+                        self.expect_value(&return_type, value, "return value");
+                        self.settle_default(value);
+                    }
+                    None => self.expect_type(&return_type, &Some(VOID.to_string()), span, "return value"),
+                }
+                Some(VOID.to_string())
+            }
+            Some(NodeValue::If { condition, then_body, else_body }) => {
+                let condition_type = self.check(condition);
+                self.expect_type(BOOLEAN, &condition_type, condition.span, "condition");
+                self.check_block(then_body);
+                if let Some(else_body) = else_body {
+                    self.check_block(else_body);
+                }
+                Some(VOID.to_string())
+            }
+            Some(NodeValue::While { condition, body }) => {
+                let condition_type = self.check(condition);
+                self.expect_type(BOOLEAN, &condition_type, condition.span, "condition");
+                self.check_block(body);
+                Some(VOID.to_string())
+            }
+            None if matches!(node.node_type, NodeType::Break | NodeType::Continue) => Some(VOID.to_string()),
+            Some(NodeValue::FunctionDefinition { name, params, return_type, body }) => {
+                if name == "main" {
+                    self.check_main(params, return_type, span);
+                }
+                let enclosing_return_type = self.return_type.replace(return_type.clone());
+                self.variables.push_scope();
+                for param in params.iter() {
+                    self.variables
+                        .set(&Node::from_symbol(&param.name), Node::from_symbol(&param.param_type))
+                        .unwrap();
+                }
+                self.variables.push_scope();
+                let mut body_type = Some(VOID.to_string());
+                for stmt in body.iter_mut() {
+                    body_type = self.check(stmt);
+                }
+                self.variables.pop_scope();
+                self.variables.pop_scope();
+                self.return_type = enclosing_return_type;
+
+                if always_returns(body) {
+                    // The `return` itself was checked against the signature.
+                } else if return_type != VOID && return_type != UNKNOWN && body_type.as_deref() == Some(VOID) {
+                    let mut err = Diagnostic::error(
+                        ErrorCode::TypeMismatch,
+                        span,
+                        format!("{} must return {} but its body ends without a value", name, return_type),
+                    );
+                    if let Some(last) = body.last() {
+                        err = err.with_label(last.span, "this statement has no value");
+                    }
+                    self.diagnostics.push(err);
+                } else if let (false, Some(last)) = (return_type == VOID, body.last_mut()) {
+                    self.expect_value(&return_type.clone(), last, "return value");
+                }
+                for stmt in body.iter_mut() {
+                    self.settle_default(stmt);
+                }
+                Some(VOID.to_string())
+            }
+            _ => None,
+        };
+        node.type_name = type_name.clone();
+        type_name
+    }
+}
+
+/// A help message listing the fields of the struct `name`.
+fn describe_fields(name: &str, fields: &[Field]) -> String {
+    let names: Vec<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+    match names.split_last() {
+        None => format!("{} has no fields", name),
+        Some((only, [])) => format!("the only field of {} is {}", name, only),
+        Some((last, rest)) => format!("the fields of {} are {} and {}", name, rest.join(", "), last),
+    }
+}
+
+/// Whether running `body` always ends in a `return`: either it ends with
+/// one, or it ends with an `if` whose branches both always return.
+fn always_returns(body: &[Node]) -> bool {
+    match body.last().map(|last| &last.value) {
+        Some(Some(NodeValue::Return { .. })) => true,
+        Some(Some(NodeValue::If { then_body, else_body: Some(else_body), .. })) => {
+            always_returns(then_body) && always_returns(else_body)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::codes;
+
+    #[test]
+    fn an_unknown_type_is_reported_once() {
+        assert_eq!(codes("defun double (x : integr) : integr { x * 2 }"), [ErrorCode::UnknownType; 2]);
+        assert_eq!(
+            codes("defun double (x : integr) : integr { x * 2 }\ndefun main () : integer { double(1) + 1 }"),
+            [ErrorCode::UnknownType; 2]
+        );
+        assert_eq!(codes("defun main () : integer { x : integr = 1\n x + 1 }"), [ErrorCode::UnknownType]);
+        assert_eq!(
+            codes("struct P { a : integr }\ndefun main () : integer { p : P = P { a: 1 }\n p.a * 2 }"),
+            [ErrorCode::UnknownType]
+        );
+    }
+
+    #[test]
+    fn mismatched_operands_are_reported_once() {
+        assert_eq!(codes("d : f64 = 1 + 2.0"), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes("a : u8 = 1\nb : i32 = 2\nc : u8 = a * b + 3"), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes("a : u8 = 1\nb : i32 = 2\nc : boolean = a < b"), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes("a : integer = \"x\" + 1"), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes("a : boolean = 1 < 2 < 3"), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes("a : integer = 1.5 | 2"), [ErrorCode::TypeMismatch]);
+    }
+
+    #[test]
+    fn main_takes_nothing_and_returns_an_integer_or_void() {
+        assert_eq!(codes("defun main () { x : integer = 7 }"), []);
+        assert_eq!(codes("defun main () : u8 { 7 }"), []);
+        assert_eq!(codes("defun main () : f64 { 3.9 }"), [ErrorCode::InvalidMain]);
+        assert_eq!(codes("defun main () : string { \"hi\" }"), [ErrorCode::InvalidMain]);
+        assert_eq!(codes("defun main (x: integer) : integer { x }"), [ErrorCode::InvalidMain]);
+    }
+
+    #[test]
+    fn a_struct_literal_initializes_each_field_once() {
+        let source = |literal: &str| {
+            format!("struct P {{ a : integer, b : integer }}\ndefun main () : integer {{ p : P = {}\n p.a }}", literal)
+        };
+        assert_eq!(codes(&source("P { b: 2, a: 1 }")), []);
+        assert_eq!(codes(&source("P { a: 1, b: 2, c: 3 }")), [ErrorCode::UnknownField]);
+        assert_eq!(codes(&source("P { a: 1 }")), [ErrorCode::MissingField]);
+        assert_eq!(codes(&source("P { a: 1, a: 2, b: 3 }")), [ErrorCode::DuplicateDeclaration]);
+    }
+
+    #[test]
+    fn fields_are_checked_through_nested_structs() {
+        let types = "struct I { v : integer }\nstruct O { i : I }\n";
+        assert_eq!(codes(&format!("{}defun main () : integer {{ o : O\n o.i.v := 4\n o.i.v }}", types)), []);
+        assert_eq!(codes(&format!("{}defun main () : integer {{ o : O\n o.i.w := 4\n 0 }}", types)), [ErrorCode::UnknownField]);
+        assert_eq!(codes(&format!("{}defun main () : integer {{ o : O\n o.i.v := 1.5\n 0 }}", types)), [ErrorCode::TypeMismatch]);
+        assert_eq!(codes(&format!("{}defun main () : integer {{ o : O\n o.i.v.x }}", types)), [ErrorCode::UnknownField]);
+    }
+
+    #[test]
+    fn an_unknown_return_type_needs_no_value() {
+        assert_eq!(codes("defun f () : integr { x : integer = 1 }"), [ErrorCode::UnknownType]);
+    }
+}